@@ -1,8 +1,37 @@
 //! Code generation logic for cranium.
 
-use std::collections::HashMap;
-
-use crate::treesitter_wrapper::*;
+mod arithmetic;
+mod array;
+mod builtin;
+mod dispatch;
+mod enumeration;
+mod globals;
+mod goto;
+mod printf;
+mod string;
+mod structure;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, iter, slice,
+};
+
+use crate::{diagnostic::Diagnostic, treesitter_wrapper::*};
+
+use self::{
+    arithmetic::Arithmetic,
+    array::{Rail, array_type},
+    builtin::Builtin,
+    dispatch::{Dispatch, block_calls, expression_splits, splits},
+    globals::{Globals, is_static},
+    string::string_value,
+    structure::Struct,
+};
+
+/// Result of generating code for (or otherwise inspecting) a
+/// node, failing with the diagnostic that explains why the
+/// node couldn't be compiled.
+type CodegenResult<T = ()> = Result<T, Diagnostic>;
 
 /// Stateful type keeping track of the C to BF code
 /// generation.
@@ -14,6 +43,14 @@ pub struct Codegen {
     stack_pointer: usize,
     /// Output BF code.
     output: String,
+    /// Problems encountered so far. Generation carries on past
+    /// a failed statement so that every problem gets reported.
+    diagnostics: Vec<Diagnostic>,
+    /// Blocks of the dispatch loop that functions which can't be
+    /// inlined are generated into.
+    dispatch: Dispatch,
+    /// The region holding global variables and `static` locals.
+    globals: Globals,
 }
 
 /// Information about a scope's variables, regarding
@@ -29,92 +66,537 @@ struct Environment<'a> {
     stack_base: usize,
     /// Maps variable name to absolute location and type.
     variables: HashMap<String, (usize, ValueType)>,
+    /// Maps the name of each variable with static storage (i.e., a
+    /// global or `static` local) to its location within the globals
+    /// region and type.
+    globals: HashMap<String, (usize, ValueType)>,
+    /// Location of the globals region within the function this
+    /// environment belongs to the body of, if it's not the region
+    /// itself but a copy of it (see `globals`).
+    globals_base: Option<usize>,
     /// Maps `typedef`-created type name to the `ValueType`.
     types: HashMap<String, ValueType>,
+    /// Maps the tag of each `struct` defined here to its type.
+    structs: HashMap<String, ValueType>,
+    /// Tags of the `enum`s defined here, which are all `int`s.
+    enums: HashSet<String>,
+    /// Maps the name of each enumerator defined here to its value.
+    constants: HashMap<String, i64>,
+    /// Where each type specifier whose tag was added here begins, as
+    /// the declarations lowered from one with several declarators
+    /// (e.g., `struct p { char x; } a, b;`) all share it.
+    specifiers: HashSet<usize>,
+    /// Maps function name to its definition. Only the top-level
+    /// environment has any.
+    functions: HashMap<String, Function<'a>>,
+    /// The function call this environment belongs to the body of,
+    /// if its `return` statements need somewhere to go.
+    frame: Option<Frame>,
+    /// The loop this environment belongs to the body of, if its
+    /// `break` and `continue` statements need somewhere to go.
+    r#loop: Option<Loop>,
+    /// The labels of the function body this environment belongs to,
+    /// if its `goto` statements need somewhere to go.
+    goto: Option<Goto>,
+}
+
+/// A user-defined function, whose body gets generated anew
+/// (i.e., inlined) at every call unless it's dispatched.
+struct Function<'a> {
+    /// Name of the function, as in its definition.
+    name: &'a Identifier,
+    /// The definition node, whose body gets inlined.
+    definition: &'a FunctionDefinition,
+    /// The type of the value the function returns.
+    return_type: ValueType,
+    /// Name and type of each parameter, in order.
+    parameters: Vec<(&'a Identifier, ValueType)>,
+    /// Whether the function is recursive, or calls a function that
+    /// is, in which case it's generated once as blocks of the dispatch
+    /// loop (see `dispatch`) rather than inlined.
+    dispatched: bool,
+}
+
+/// Where a function call keeps track of its `return`.
+struct Frame {
+    /// Location of the space for the return value.
+    result: usize,
+    /// The type of the return value.
+    return_type: ValueType,
+    /// How `return` gets out of the function.
+    exit: Return,
+}
+
+/// The way a function's `return` statements get out of it.
+#[derive(Clone, Copy)]
+enum Return {
+    /// An inlined function clears the cell at `running` (nonzero
+    /// until then), which gates everything after a `return`.
+    Gated { running: usize },
+    /// A dispatched function clears its frame from `locals` on, then
+    /// ends the block, handing control back to the caller's block
+    /// whose number is at `ret`.
+    Dispatched { ret: usize, locals: usize },
+}
+
+impl Frame {
+    /// Returns the cell that gates whatever comes after a `return`,
+    /// if there is one.
+    fn running(&self) -> Option<usize> {
+        match self.exit {
+            Return::Gated { running } => Some(running),
+            Return::Dispatched { .. } => None,
+        }
+    }
+}
+
+/// The way a loop's `break` and `continue` statements get out of it
+/// (or out of the current iteration), or a `switch`'s `break`.
+#[derive(Clone, Copy)]
+enum Loop {
+    /// `break` clears the cells at both `looping` (which gates the
+    /// condition, and is nonzero until then) and `iterating`, while
+    /// `continue` only clears the latter. That one is set at the start
+    /// of every iteration and gates everything after either statement.
+    /// A `return` clears both as well.
+    Gated { looping: usize, iterating: usize },
+    /// Within a dispatched function, both clear the stack down to
+    /// `top`, then end the block, jumping to the block numbered `exit`
+    /// (for `break`) or `next` (for `continue`).
+    Dispatched {
+        top: usize,
+        exit: usize,
+        next: usize,
+    },
+    /// A `switch` (which `continue` passes through, to the loop it's
+    /// in) has everything within it gated by the cell at `matched`,
+    /// which is set upon reaching the label it jumps to. Any statement
+    /// getting out of it clears that.
+    Switch { matched: usize },
+    /// A `switch` within a dispatched function that had to be split
+    /// into blocks, which `break` gets out of like a dispatched loop.
+    DispatchedSwitch { top: usize, exit: usize },
+}
+
+impl Loop {
+    /// Returns whether it's a loop, rather than a `switch`.
+    fn is_loop(self) -> bool {
+        matches!(self, Self::Gated { .. } | Self::Dispatched { .. })
+    }
+
+    /// Returns the cells that stop it (along with everything within
+    /// it) once cleared, if it's gated.
+    fn flags(self) -> Vec<usize> {
+        match self {
+            Self::Gated { looping, iterating } => vec![looping, iterating],
+            Self::Switch { matched } => vec![matched],
+            Self::Dispatched { .. } | Self::DispatchedSwitch { .. } => vec![],
+        }
+    }
+}
+
+/// The way a `goto` statement gets to its label, which has to be
+/// directly within the body of the function (see `goto`). Each label
+/// has a number in `labels`.
+enum Goto {
+    /// An inlined function's body runs in a loop for as long as the
+    /// cell at `pc` is nonzero, each iteration starting from the label
+    /// whose number it holds (clearing it). Everything is gated by the
+    /// cell at `matched`, which is set upon reaching that label, so
+    /// `goto` sets `pc` and clears `matched` (and any loop's cells).
+    Gated {
+        pc: usize,
+        matched: usize,
+        labels: HashMap<String, usize>,
+    },
+    /// Within a dispatched function, each label starts a block (the one
+    /// with its number), which `goto` clears the stack down to `top`
+    /// and jumps to.
+    Dispatched {
+        top: usize,
+        labels: HashMap<String, usize>,
+    },
 }
 
-#[derive(Clone, Copy, PartialEq)]
-/// The type associated with a value.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// The type associated with a value. Integer types are ordered
+/// by rank (i.e., `char` < `short` < `int` < `long`), each signed
+/// type just before its unsigned counterpart.
+///
+/// Signed integers are stored in two's complement, so they only
+/// differ from unsigned ones when converted or compared.
 enum ValueType {
     /// Void type (no return). Size 0.
     Void,
     /// Boolean type (true or false). Size 1.
     Bool,
-    /// The 8-bit integer type. Unsigned, but that's contentious.
-    // TODO: real C chars are signed by default
+    /// The signed 8-bit integer type. Plain `char` is signed (as on
+    /// most platforms) and `signed char` is the same type.
     Char,
-    // TODO: structs, typedefs, etc.
+    /// The unsigned 8-bit integer type.
+    UnsignedChar,
+    /// Signed 16-bit integer type. Size 2.
+    Short,
+    /// Unsigned 16-bit integer type. Size 2.
+    UnsignedShort,
+    /// Signed 16-bit integer type, same as `short` since every extra
+    /// cell makes arithmetic that much slower. Size 2.
+    Int,
+    /// Unsigned 16-bit integer type. Size 2.
+    UnsignedInt,
+    /// Signed 32-bit integer type. Size 4.
+    Long,
+    /// Unsigned 32-bit integer type. Size 4.
+    UnsignedLong,
+    /// Fixed-size array of some number of elements, laid out one
+    /// after the other. Variables holding arrays also get a rail
+    /// (see `array`) in front of them.
+    Array(Box<ValueType>, usize),
+    /// Record of named fields, laid out one after the other (see
+    /// `structure`).
+    Struct(Box<Struct>),
 }
 
 impl ValueType {
     /// Returns the `ValueType` associated with a type specifier node and the environment it occured within.
-    fn from_type_specifier(spec: &TypeSpecifier, env: &Environment) -> Self {
+    fn from_type_specifier(spec: &TypeSpecifier, env: &Environment) -> CodegenResult<Self> {
         match *spec {
             TypeSpecifier::PrimitiveType(ref prim) => match prim.src.as_str() {
-                "char" => Self::Char,
-                "bool" => Self::Bool,
-                "void" => Self::Void,
-                _ => panic!("Unknown primitive type specifier encountered: {}", prim.src),
+                "char" => Ok(Self::Char),
+                "int" => Ok(Self::Int),
+                "bool" => Ok(Self::Bool),
+                "void" => Ok(Self::Void),
+                _ => Err(Diagnostic::new(
+                    format!("unsupported type `{}`", prim.src),
+                    prim.span,
+                )),
             },
-            TypeSpecifier::TypeIdentifier(ref id) => {
-                env.lookup_type(&id.src).expect("Type should be defined")
+            // e.g., `short`, `unsigned long int`
+            TypeSpecifier::SizedTypeSpecifier(ref sized) => {
+                let words: Vec<_> = sized.src.split_whitespace().collect();
+                let unsupported = || {
+                    Diagnostic::new(
+                        format!("unsupported type `{}`", words.join(" ")),
+                        sized.span,
+                    )
+                };
+
+                let mut signed = None;
+                let mut size_words = vec![];
+
+                for &word in &words {
+                    match word {
+                        "signed" | "unsigned" if signed.is_none() => {
+                            signed = Some(word == "signed");
+                        }
+                        "short" | "long" | "int" | "char" => size_words.push(word),
+                        _ => return Err(unsupported()),
+                    }
+                }
+
+                size_words.sort_unstable();
+
+                let r#type = match size_words.as_slice() {
+                    ["char"] => Self::Char,
+                    [] | ["int"] => Self::Int,
+                    ["short"] | ["int", "short"] => Self::Short,
+                    ["long"] | ["int", "long"] => Self::Long,
+                    _ => return Err(unsupported()),
+                };
+
+                Ok(match signed {
+                    Some(false) => r#type.to_unsigned(),
+                    _ => r#type,
+                })
             }
+            TypeSpecifier::TypeIdentifier(ref id) => env
+                .lookup_type(&id.src)
+                .ok_or_else(|| Diagnostic::new(format!("unknown type name `{}`", id.src), id.span)),
+            TypeSpecifier::StructSpecifier(ref spec) => Self::from_struct_specifier(spec, env),
+            TypeSpecifier::EnumSpecifier(ref spec) => Self::from_enum_specifier(spec, env),
         }
     }
 
     /// Gets the type associated with an expression given the environment it occurred within.
-    fn from_expression(expr: &Expression, env: &Environment) -> Self {
+    fn from_expression(expr: &Expression, env: &Environment) -> CodegenResult<Self> {
         match *expr {
-            Expression::Identifier(ref id) => {
-                env.lookup_variable(&id.src)
-                    .expect("Variable should be defined")
-                    .1
-            }
-            Expression::AssignmentExpression(ref it) => {
-                env.lookup_variable(&it.left.src)
-                    .expect("Variable should be defined")
-                    .1
+            Expression::Identifier(ref id) if env.lookup_constant(&id.src).is_some() => {
+                Ok(Self::Int)
             }
+            Expression::Identifier(ref id) => Ok(env.variable(id)?.1),
+            Expression::AssignmentExpression(ref it) => Self::from_expression(&it.left, env),
             Expression::BinaryExpression(ref binexpr) => Self::from_binary_expression(binexpr, env),
-            // if i ever do functions this will be a bit more involved
-            Expression::CallExpression(ref call) => match call.function.src.as_str() {
-                "putchar" => Self::Void,
-                _ => unimplemented!("Only supporting putchar function"),
+            Expression::CallExpression(ref call) => match Builtin::from_name(&call.function.src) {
+                Some(builtin) => Ok(builtin.return_type()),
+                None => Ok(env.function(&call.function)?.return_type.clone()),
             },
-            Expression::CharLiteral(_) | Expression::NumberLiteral(_) => Self::Char,
-            Expression::True | Expression::False => Self::Bool,
+            Expression::CharLiteral(_) => Ok(Self::Char),
+            // the terminating zero included
+            Expression::StringLiteral(ref sl) => Ok(Self::Array(
+                Box::new(Self::Char),
+                string_value(sl)?.len() + 1,
+            )),
+            Expression::NumberLiteral(ref nl) => Ok(interpret_number_literal(nl)?.1),
+            Expression::True(_) | Expression::False(_) => Ok(Self::Bool),
             // these guys still disgust me
-            Expression::UpdateExpression(_) => Self::Char,
+            Expression::UpdateExpression(ref update_expr) => {
+                Self::from_expression(&update_expr.argument, env)
+            }
+            Expression::UnaryExpression(ref unary_expr) => {
+                Self::from_unary_expression(unary_expr, env)
+            }
             Expression::ParenthesizedExpression(ref paren_expr) => {
                 Self::from_expression(&paren_expr.child, env)
             }
+            Expression::SubscriptExpression(ref subscript_expr) => {
+                Self::from_subscript_expression(subscript_expr, env)
+            }
+            Expression::FieldExpression(ref field_expr) => {
+                Self::from_field_expression(field_expr, env)
+            }
+            Expression::ConditionalExpression(ref cond_expr) => {
+                Self::from_conditional_expression(cond_expr, env)
+            }
+            Expression::CommaExpression(ref comma_expr) => {
+                Self::from_expression(&comma_expr.right, env)
+            }
         }
     }
 
+    /// Returns the result type of a conditional expression occuring
+    /// within `env`, which its branches are both converted to.
+    fn from_conditional_expression(
+        cond_expr: &ConditionalExpression,
+        env: &Environment,
+    ) -> CodegenResult<Self> {
+        Codegen::check_condition(&cond_expr.condition, env)?;
+
+        let consequence_type = Self::from_expression(&cond_expr.consequence, env)?;
+        let alternative_type = Self::from_expression(&cond_expr.alternative, env)?;
+
+        Self::common_type(&consequence_type, &alternative_type).ok_or_else(|| {
+            Diagnostic::new(
+                format!(
+                    "mismatched types `{consequence_type}` and `{alternative_type}` in conditional expression"
+                ),
+                cond_expr.span,
+            )
+        })
+    }
+
     /// Returns the result type of a binary expression occuring within `env`.
-    fn from_binary_expression(binary_expr: &BinaryExpression, env: &Environment) -> Self {
-        let left_type = Self::from_expression(&binary_expr.left, env);
-        let right_type = Self::from_expression(&binary_expr.right, env);
+    fn from_binary_expression(
+        binary_expr: &BinaryExpression,
+        env: &Environment,
+    ) -> CodegenResult<Self> {
+        // the operands of these needn't have anything in common
+        if let BinaryOperator::LogicalAnd(_) | BinaryOperator::LogicalOr(_) = *binary_expr.operator
+        {
+            Codegen::check_condition(&binary_expr.left, env)?;
+            Codegen::check_condition(&binary_expr.right, env)?;
 
-        if left_type != right_type {
-            unimplemented!("not doing cross-type operators");
+            return Ok(Self::Bool);
         }
 
+        let operand_type = Self::operand_type(binary_expr, env)?;
+
         match *binary_expr.operator {
-            BinaryOperator::EqualsCheck | BinaryOperator::NotEqualsCheck => Self::Bool,
-            BinaryOperator::Plus | BinaryOperator::Minus => {
+            BinaryOperator::LogicalAnd(_) | BinaryOperator::LogicalOr(_) => {
+                unreachable!("handled above")
+            }
+            BinaryOperator::EqualsCheck(_) | BinaryOperator::NotEqualsCheck(_) => {
+                if !(operand_type.is_integer() || operand_type == Self::Bool) {
+                    return Err(Diagnostic::new(
+                        format!("cannot compare values of type `{operand_type}`"),
+                        binary_expr.span,
+                    ));
+                }
+
+                Ok(Self::Bool)
+            }
+            BinaryOperator::LessThan(_)
+            | BinaryOperator::GreaterThan(_)
+            | BinaryOperator::LessThanOrEqual(_)
+            | BinaryOperator::GreaterThanOrEqual(_) => {
+                // bools are ordered too (`false < true`)
+                if !(operand_type.is_integer() || operand_type == Self::Bool) {
+                    return Err(Diagnostic::new(
+                        format!("cannot order values of type `{operand_type}`"),
+                        binary_expr.span,
+                    ));
+                }
+
+                Ok(Self::Bool)
+            }
+            BinaryOperator::Plus(_)
+            | BinaryOperator::Minus(_)
+            | BinaryOperator::Times(_)
+            | BinaryOperator::Divide(_)
+            | BinaryOperator::Modulo(_)
+            | BinaryOperator::BitwiseAnd(_)
+            | BinaryOperator::BitwiseOr(_)
+            | BinaryOperator::BitwiseXor(_)
+            | BinaryOperator::ShiftLeft(_)
+            | BinaryOperator::ShiftRight(_) => {
                 // integer types only.
                 // in C, bools can also do this
                 // because they dont exist and are
                 // really just 1-byte integers
                 // which is silly but idc and im not gonna care
-                assert!(
-                    matches!(left_type, Self::Char),
-                    "non-integer type used for addition or subtraction"
-                );
+                if !operand_type.is_integer() {
+                    return Err(Diagnostic::new(
+                        format!("cannot do arithmetic with values of type `{operand_type}`"),
+                        binary_expr.span,
+                    ));
+                }
+
+                Ok(operand_type)
+            }
+        }
+    }
+
+    /// Returns the result type of a unary expression occuring within `env`.
+    fn from_unary_expression(
+        unary_expr: &UnaryExpression,
+        env: &Environment,
+    ) -> CodegenResult<Self> {
+        if let UnaryOperator::Not(_) = *unary_expr.operator {
+            Codegen::check_condition(&unary_expr.argument, env)?;
+
+            return Ok(Self::Bool);
+        }
+
+        let r#type = Self::from_expression(&unary_expr.argument, env)?;
+
+        if !r#type.is_integer() {
+            return Err(Diagnostic::new(
+                format!(
+                    "cannot apply unary `{}` to a value of type `{type}`",
+                    match *unary_expr.operator {
+                        UnaryOperator::Plus(_) => '+',
+                        UnaryOperator::Minus(_) => '-',
+                        UnaryOperator::BitwiseNot(_) => '~',
+                        UnaryOperator::Not(_) => unreachable!("handled above"),
+                    }
+                ),
+                unary_expr.span,
+            ));
+        }
+
+        Ok(r#type.promoted())
+    }
+
+    /// Returns the type that both operands of a binary expression
+    /// are converted to before the operation. As in C, integers are
+    /// promoted (see `promoted`), then meet at the larger of the two,
+    /// which is unsigned if they're the same size and either is
+    /// unsigned. A shift is done in the promoted type of the value
+    /// being shifted instead, which its count is converted to.
+    fn operand_type(binary_expr: &BinaryExpression, env: &Environment) -> CodegenResult<Self> {
+        let left_type = Self::from_expression(&binary_expr.left, env)?;
+        let right_type = Self::from_expression(&binary_expr.right, env)?;
+
+        if let BinaryOperator::ShiftLeft(_) | BinaryOperator::ShiftRight(_) = *binary_expr.operator
+            && left_type.is_integer()
+            && right_type.is_integer()
+        {
+            return Ok(left_type.promoted());
+        }
+
+        Self::common_type(&left_type, &right_type).ok_or_else(|| {
+            Diagnostic::new(
+                format!("mismatched types `{left_type}` and `{right_type}` in binary expression"),
+                binary_expr.span,
+            )
+        })
+    }
 
-                Self::Char
+    /// Returns the type that values of types `left` and `right` are
+    /// both converted to when they meet (see `operand_type`), if they
+    /// can meet at all.
+    fn common_type(left: &Self, right: &Self) -> Option<Self> {
+        if left.is_integer() && right.is_integer() {
+            let (left, right) = (left.promoted(), right.promoted());
+            let larger = left.clone().max(right.clone());
+
+            if left.size() == right.size() && !(left.is_signed() && right.is_signed()) {
+                Some(larger.to_unsigned())
+            } else {
+                Some(larger)
             }
+        } else if left == right {
+            Some(left.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether `value` is in the range of this integer type.
+    /// A negative value counts for an unsigned type if it wraps around
+    /// into it (as e.g. `-1u` does).
+    fn holds(&self, value: i64) -> bool {
+        let bits = 8 * self.size();
+
+        if self.is_signed() {
+            (-(1 << (bits - 1))..1 << (bits - 1)).contains(&value)
+        } else {
+            (-(1 << bits) + 1..1 << bits).contains(&value)
+        }
+    }
+
+    /// Returns `value` converted to this integer type, wrapping around
+    /// into its range the way the generated code's arithmetic does.
+    fn wrap(&self, value: i64) -> i64 {
+        let bits = 8 * self.size();
+        let low = value & ((1 << bits) - 1);
+
+        if self.is_signed() && low >> (bits - 1) != 0 {
+            low - (1 << bits)
+        } else {
+            low
+        }
+    }
+
+    /// Returns whether the type is one of the integer types.
+    fn is_integer(&self) -> bool {
+        matches!(
+            *self,
+            Self::Char
+                | Self::UnsignedChar
+                | Self::Short
+                | Self::UnsignedShort
+                | Self::Int
+                | Self::UnsignedInt
+                | Self::Long
+                | Self::UnsignedLong
+        )
+    }
+
+    /// Returns whether the type is a signed integer type.
+    fn is_signed(&self) -> bool {
+        matches!(*self, Self::Char | Self::Short | Self::Int | Self::Long)
+    }
+
+    /// Returns the type that an integer of this type is promoted to
+    /// before arithmetic or comparison: an `int` if it's any smaller
+    /// (or an `unsigned int` for an `unsigned short`, whose values
+    /// don't all fit in an `int`), as in C.
+    fn promoted(&self) -> Self {
+        match *self {
+            Self::Char | Self::UnsignedChar | Self::Short => Self::Int,
+            Self::UnsignedShort => Self::UnsignedInt,
+            ref other => other.clone(),
+        }
+    }
+
+    /// Returns the unsigned counterpart of an integer type.
+    fn to_unsigned(&self) -> Self {
+        match *self {
+            Self::Char => Self::UnsignedChar,
+            Self::Short => Self::UnsignedShort,
+            Self::Int => Self::UnsignedInt,
+            Self::Long => Self::UnsignedLong,
+            ref other => other.clone(),
         }
     }
 
@@ -123,96 +605,797 @@ impl ValueType {
         match *self {
             Self::Void => 0,
             Self::Bool => 1,
-            Self::Char => 1,
+            Self::Char | Self::UnsignedChar => 1,
+            Self::Short | Self::UnsignedShort | Self::Int | Self::UnsignedInt => 2,
+            Self::Long | Self::UnsignedLong => 4,
+            Self::Array(ref element, length) => element.size() * length,
+            Self::Struct(ref layout) => layout.size(),
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // e.g., `int[2][3]`, which is an array of two `int[3]`s
+        if let Self::Array(..) = *self {
+            let mut element = self;
+            let mut lengths = String::new();
+
+            while let Self::Array(ref inner, length) = *element {
+                lengths.push_str(&format!("[{length}]"));
+                element = inner;
+            }
+
+            return write!(f, "{element}{lengths}");
+        }
+
+        if let Self::Struct(ref layout) = *self {
+            return match layout.name {
+                Some(ref name) => write!(f, "struct {name}"),
+                None => f.write_str("struct <anonymous>"),
+            };
+        }
+
+        f.write_str(match *self {
+            Self::Void => "void",
+            Self::Bool => "bool",
+            Self::Char => "char",
+            Self::UnsignedChar => "unsigned char",
+            Self::Short => "short",
+            Self::UnsignedShort => "unsigned short",
+            Self::Int => "int",
+            Self::UnsignedInt => "unsigned int",
+            Self::Long => "long",
+            Self::UnsignedLong => "unsigned long",
+            Self::Array(..) | Self::Struct(_) => unreachable!("handled above"),
+        })
+    }
+}
+
+/// Returns the value of a number literal (which includes its sign,
+/// if any) and its type, failing if it isn't a decimal, hex (`0x`) or
+/// octal (leading `0`) integer with at most a `u` and an `l` suffix.
+///
+/// As in C, the type is the first of a list that holds the value:
+/// `int` then `long` for a decimal literal, with `unsigned int` and
+/// `unsigned long` after each of those for a hex or octal one. A `u`
+/// leaves only the unsigned types and an `l` only the `long`s. Decimal
+/// literals too big for a `long` still get to be an `unsigned long`.
+fn interpret_number_literal(nl: &NumberLiteral) -> CodegenResult<(i64, ValueType)> {
+    let unsupported =
+        || Diagnostic::new(format!("unsupported number literal `{}`", nl.src), nl.span);
+
+    let src = nl.src.to_ascii_lowercase();
+    let (negative, src) = match src.strip_prefix('-') {
+        Some(src) => (true, src),
+        None => (false, src.strip_prefix('+').unwrap_or(&src)),
+    };
+
+    let digits = src.trim_end_matches(['u', 'l']);
+    let (unsigned, long) = match &src[digits.len()..] {
+        "" => (false, false),
+        "u" => (true, false),
+        "l" => (false, true),
+        "ul" | "lu" => (true, true),
+        _ => return Err(unsupported()),
+    };
+
+    let (digits, radix) = if let Some(hex) = digits.strip_prefix("0x") {
+        (hex, 16)
+    } else if let Some(octal) = digits.strip_prefix('0').filter(|octal| !octal.is_empty()) {
+        (octal, 8)
+    } else {
+        (digits, 10)
+    };
+
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(unsupported());
+    }
+
+    let candidates: &[ValueType] = match (radix == 10, unsigned, long) {
+        (true, false, false) => &[ValueType::Int, ValueType::Long, ValueType::UnsignedLong],
+        (true, false, true) => &[ValueType::Long, ValueType::UnsignedLong],
+        (false, false, false) => &[
+            ValueType::Int,
+            ValueType::UnsignedInt,
+            ValueType::Long,
+            ValueType::UnsignedLong,
+        ],
+        (false, false, true) => &[ValueType::Long, ValueType::UnsignedLong],
+        (_, true, false) => &[ValueType::UnsignedInt, ValueType::UnsignedLong],
+        (_, true, true) => &[ValueType::UnsignedLong],
+    };
+
+    let value = i64::from_str_radix(digits, radix)
+        .ok()
+        .map(|value| if negative { -value } else { value });
+    let r#type = value.and_then(|value| candidates.iter().find(|r#type| r#type.holds(value)));
+
+    match (value, r#type) {
+        (Some(value), Some(r#type)) => Ok((value, r#type.clone())),
+        _ => Err(Diagnostic::new(
+            format!(
+                "literal `{}` out of range for `{}`",
+                nl.src,
+                candidates[candidates.len() - 1]
+            ),
+            nl.span,
+        )),
+    }
+}
+
+/// Returns the byte that an escape sequence (e.g., `\n`, `\0` or
+/// `\xff`) within a character or string literal stands for.
+fn escape_value(es: &EscapeSequence) -> CodegenResult<u8> {
+    let unsupported =
+        || Diagnostic::new(format!("unsupported escape sequence `{}`", es.src), es.span);
+
+    let (digits, radix) = match es.src.as_str() {
+        r"\'" => return Ok(b'\''),
+        r#"\""# => return Ok(b'\"'),
+        r"\?" => return Ok(b'?'),
+        r"\\" => return Ok(b'\\'),
+        r"\a" => return Ok(0x07),
+        r"\b" => return Ok(0x08),
+        r"\f" => return Ok(0x0c),
+        r"\n" => return Ok(b'\n'),
+        r"\r" => return Ok(b'\r'),
+        r"\t" => return Ok(b'\t'),
+        r"\v" => return Ok(0x0b),
+        esc => match esc.strip_prefix(r"\x") {
+            Some(digits) => (digits, 16),
+            None => (&esc[1..], 8),
+        },
+    };
+
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(unsupported());
+    }
+
+    // octal escapes have up to three digits, so only `\400` and above
+    // (or a hex escape over `\xff`) don't fit in a byte
+    u32::from_str_radix(digits, radix)
+        .ok()
+        .and_then(|value| u8::try_from(value).ok())
+        .ok_or_else(|| {
+            Diagnostic::new(
+                format!("escape sequence `{}` out of range", es.src),
+                es.span,
+            )
+        })
+}
+
+/// Returns the value of a character literal, failing unless it's a
+/// single character (or escape sequence) that fits in a `char`.
+fn char_literal_value(node: &CharLiteral) -> CodegenResult<u8> {
+    let [child] = node.children.as_slice() else {
+        return Err(Diagnostic::new(
+            "character literal must contain exactly one character",
+            node.span,
+        ));
+    };
+
+    let c = match *child {
+        CharLiteralChildren::Character(ref c) => {
+            let mut chars = c.src.chars();
+
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => {
+                    return Err(Diagnostic::new(
+                        "character literal must contain exactly one character",
+                        node.span,
+                    ));
+                }
+            }
+        }
+        CharLiteralChildren::EscapeSequence(ref es) => return escape_value(es),
+    };
+
+    u8::try_from(c).map_err(|_| {
+        Diagnostic::new(
+            format!("character `{c}` does not fit in a `char`"),
+            node.span,
+        )
+    })
+}
+
+/// Returns the value of `expr` (occurring within `env`) if it's a
+/// constant, as array sizes, indices known at compile time,
+/// enumerator values, `case` labels and global initializers are.
+/// Operators are folded the way the generated code would work them
+/// out, wrapping around to the type of each subexpression.
+fn constant_value(expr: &Expression, env: &Environment) -> Option<i64> {
+    let value = match *expr {
+        Expression::NumberLiteral(ref nl) => interpret_number_literal(nl).ok()?.0,
+        // `char` is signed, so e.g. `'\xff'` is -1
+        Expression::CharLiteral(ref cl) => char_literal_value(cl).ok()?.cast_signed().into(),
+        Expression::Identifier(ref id) => env.lookup_constant(&id.src)?,
+        Expression::True(_) => 1,
+        Expression::False(_) => 0,
+        Expression::ParenthesizedExpression(ref pe) => constant_value(&pe.child, env)?,
+        Expression::UnaryExpression(ref ue) => {
+            let argument = constant_value(&ue.argument, env)?;
+
+            match *ue.operator {
+                UnaryOperator::Plus(_) => argument,
+                UnaryOperator::Minus(_) => argument.wrapping_neg(),
+                UnaryOperator::Not(_) => (argument == 0).into(),
+                UnaryOperator::BitwiseNot(_) => !argument,
+            }
+        }
+        Expression::BinaryExpression(ref be) => {
+            // also checks that the operands suit the operator
+            ValueType::from_binary_expression(be, env).ok()?;
+
+            let left = constant_value(&be.left, env)?;
+            let right = constant_value(&be.right, env)?;
+
+            // the operands of these needn't have anything in common
+            match *be.operator {
+                BinaryOperator::LogicalAnd(_) => return Some((left != 0 && right != 0).into()),
+                BinaryOperator::LogicalOr(_) => return Some((left != 0 || right != 0).into()),
+                _ => {}
+            }
+
+            let operand_type = ValueType::operand_type(be, env).ok()?;
+            let (left, right) = (operand_type.wrap(left), operand_type.wrap(right));
+            // shifting by the count's lowest byte (see `Arithmetic`), capped
+            // where it's past the widest type anyway
+            let count = u32::from(right.to_le_bytes()[0]).min(63);
+
+            match *be.operator {
+                BinaryOperator::EqualsCheck(_) => (left == right).into(),
+                BinaryOperator::NotEqualsCheck(_) => (left != right).into(),
+                BinaryOperator::LessThan(_) => (left < right).into(),
+                BinaryOperator::GreaterThan(_) => (left > right).into(),
+                BinaryOperator::LessThanOrEqual(_) => (left <= right).into(),
+                BinaryOperator::GreaterThanOrEqual(_) => (left >= right).into(),
+                BinaryOperator::Plus(_) => left.wrapping_add(right),
+                BinaryOperator::Minus(_) => left.wrapping_sub(right),
+                BinaryOperator::Times(_) => left.wrapping_mul(right),
+                // dividing by zero gives -1, and `x % 0` gives `x`
+                BinaryOperator::Divide(_) if right == 0 => -1,
+                BinaryOperator::Divide(_) => left.wrapping_div(right),
+                BinaryOperator::Modulo(_) if right == 0 => left,
+                BinaryOperator::Modulo(_) => left.wrapping_rem(right),
+                BinaryOperator::BitwiseAnd(_) => left & right,
+                BinaryOperator::BitwiseOr(_) => left | right,
+                BinaryOperator::BitwiseXor(_) => left ^ right,
+                BinaryOperator::ShiftLeft(_) => left << count,
+                BinaryOperator::ShiftRight(_) => left >> count,
+                BinaryOperator::LogicalAnd(_) | BinaryOperator::LogicalOr(_) => {
+                    unreachable!("handled above")
+                }
+            }
         }
+        Expression::ConditionalExpression(ref ce) => {
+            let condition = constant_value(&ce.condition, env)?;
+            let consequence = constant_value(&ce.consequence, env)?;
+            let alternative = constant_value(&ce.alternative, env)?;
+
+            if condition != 0 {
+                consequence
+            } else {
+                alternative
+            }
+        }
+        _ => return None,
+    };
+
+    match ValueType::from_expression(expr, env).ok()? {
+        r#type if r#type.is_integer() => Some(r#type.wrap(value)),
+        ValueType::Bool => Some(value),
+        _ => None,
+    }
+}
+
+/// Fails unless `call` supplies `count` arguments.
+fn check_arguments(call: &CallExpression, count: usize) -> CodegenResult {
+    let supplied = call.arguments.children.len();
+
+    if supplied != count {
+        return Err(Diagnostic::new(
+            format!(
+                "`{}` takes {count} argument{} but {supplied} were supplied",
+                call.function.src,
+                if count == 1 { "" } else { "s" },
+            ),
+            call.span,
+        ));
     }
+
+    Ok(())
 }
 
-/// Takes a declaration node and the environment it encounters in and returns the name and type it's associated with.
-fn interpret_declaration(decl: &Declaration, env: &Environment) -> (String, ValueType) {
-    /// Takes a declarator node, the type it was associated with, and the environment it occurred within and returns the associated name and type for the declarator.
-    fn interpret_declarator(declarator: &Declarator, prior_type: ValueType) -> (String, ValueType) {
+/// Complains about a call to a function that doesn't exist.
+fn unknown_function(name: &Identifier) -> Diagnostic {
+    Diagnostic::new(format!("cannot find function `{}`", name.src), name.span)
+}
+
+/// Takes a declaration node and the environment it encounters in and returns the name (as its identifier node) and type it's associated with.
+fn interpret_declaration<'d>(
+    decl: &'d Declaration,
+    env: &Environment,
+) -> CodegenResult<(&'d Identifier, ValueType)> {
+    /// Takes a declarator node, the type it was associated with, the initializer it's given (which an array's size may come from) and the environment it occurred within and returns the associated name and type for the declarator.
+    fn interpret_declarator<'d>(
+        declarator: &'d Declarator,
+        prior_type: ValueType,
+        initializer: Option<&Initializer>,
+        env: &Environment,
+    ) -> CodegenResult<(&'d Identifier, ValueType)> {
         match *declarator {
-            Declarator::Identifier(ref id) => (id.src.clone(), prior_type),
+            Declarator::Identifier(ref id) => Ok((id, prior_type)),
             Declarator::InitDeclarator(ref init) => {
-                interpret_declarator(&init.declarator, prior_type)
+                interpret_declarator(&init.declarator, prior_type, Some(&init.value), env)
+            }
+            Declarator::ArrayDeclarator(ref ad) => interpret_declarator(
+                &ad.declarator,
+                array_type(ad, prior_type, initializer, env)?,
+                initializer,
+                env,
+            ),
+            Declarator::FunctionDeclarator(ref fd) => Err(Diagnostic::new(
+                "function declarations are not supported",
+                fd.span,
+            )),
+            Declarator::TypeIdentifier(ref id) => {
+                Err(Diagnostic::new("expected a variable name", id.span))
+            }
+            Declarator::FieldIdentifier(ref id) => {
+                Err(Diagnostic::new("expected a variable name", id.span))
             }
-            Declarator::FunctionDeclarator(_) => panic!("Unexpected function declarator"),
         }
     }
 
     interpret_declarator(
         &decl.declarator,
-        ValueType::from_type_specifier(&decl.r#type, env),
+        ValueType::from_type_specifier(&decl.r#type, env)?,
+        None,
+        env,
     )
 }
 
-/// Returns the associated name and type with a `typedef` statement.
-fn interpret_type_definition(typedef: &TypeDefinition, env: &Environment) -> (String, ValueType) {
-    fn interpret_type_declarator(
-        declarator: &TypeDeclarator,
+/// Returns the associated name (as its identifier node) and type with a `typedef` statement.
+fn interpret_type_definition<'d>(
+    typedef: &'d TypeDefinition,
+    env: &Environment,
+) -> CodegenResult<(&'d TypeIdentifier, ValueType)> {
+    /// Returns the name and type that the declarator of a `typedef`
+    /// (occurring within `env`) gives a type (`prior_type`), which it
+    /// may make an array of.
+    fn interpret_type_declarator<'d>(
+        declarator: &'d TypeDeclarator,
         prior_type: ValueType,
-    ) -> (String, ValueType) {
+        env: &Environment,
+    ) -> CodegenResult<(&'d TypeIdentifier, ValueType)> {
         match *declarator {
-            TypeDeclarator::TypeIdentifier(ref id) => (id.src.clone(), prior_type),
-            // TODO: more declarator variants (prolly just array...)
+            TypeDeclarator::TypeIdentifier(ref id) => Ok((id, prior_type)),
+            TypeDeclarator::ArrayDeclarator(ref ad) => {
+                interpret_array_declarator(ad, prior_type, env)
+            }
+        }
+    }
+
+    /// Like `interpret_type_declarator`, but for whatever an array
+    /// declarator within a `typedef` declares an array of.
+    fn interpret_array_declarator<'d>(
+        declarator: &'d ArrayDeclarator,
+        prior_type: ValueType,
+        env: &Environment,
+    ) -> CodegenResult<(&'d TypeIdentifier, ValueType)> {
+        let r#type = array_type(declarator, prior_type, None, env)?;
+
+        match *declarator.declarator {
+            Declarator::TypeIdentifier(ref id) => Ok((id, r#type)),
+            Declarator::ArrayDeclarator(ref ad) => interpret_array_declarator(ad, r#type, env),
+            ref other => Err(Diagnostic::new("expected a type name", other.span())),
         }
     }
 
     interpret_type_declarator(
         &typedef.declarator,
-        ValueType::from_type_specifier(&typedef.r#type, env),
+        ValueType::from_type_specifier(&typedef.r#type, env)?,
+        env,
     )
 }
 
+/// Takes a function definition node and the environment it occurred within and returns the function it defines.
+fn interpret_function_definition<'d>(
+    funcdef: &'d FunctionDefinition,
+    env: &Environment,
+) -> CodegenResult<Function<'d>> {
+    let Declarator::FunctionDeclarator(ref fd) = *funcdef.declarator else {
+        return Err(Diagnostic::new(
+            "expected a function declarator",
+            funcdef.declarator.span(),
+        ));
+    };
+
+    let Declarator::Identifier(ref name) = *fd.declarator else {
+        return Err(Diagnostic::new("unsupported function declarator", fd.span));
+    };
+
+    let mut parameters: Vec<(&Identifier, ValueType)> = vec![];
+
+    for param in &fd.parameters.children {
+        let r#type = ValueType::from_type_specifier(&param.r#type, env)?;
+
+        match param.declarator.as_deref() {
+            // `f(void)` takes no parameters
+            None if r#type == ValueType::Void && fd.parameters.children.len() == 1 => {}
+            Some(Declarator::Identifier(id)) if r#type == ValueType::Void => {
+                return Err(Diagnostic::new(
+                    format!("parameter `{}` cannot have type `void`", id.src),
+                    param.span,
+                ));
+            }
+            // C would pass a pointer to the array instead
+            Some(Declarator::ArrayDeclarator(_)) => {
+                return Err(Diagnostic::new(
+                    "array parameters are not supported",
+                    param.span,
+                ));
+            }
+            Some(Declarator::Identifier(_)) if matches!(r#type, ValueType::Array(..)) => {
+                return Err(Diagnostic::new(
+                    "array parameters are not supported",
+                    param.span,
+                ));
+            }
+            // there's no room for a rail in front of a parameter
+            Some(Declarator::Identifier(_)) if Rail::of(&r#type).is_some() => {
+                return Err(Diagnostic::new(
+                    format!("parameters of type `{type}` (which holds an array) are not supported"),
+                    param.span,
+                ));
+            }
+            Some(Declarator::Identifier(id)) => {
+                if parameters.iter().any(|(other, _)| other.src == id.src) {
+                    return Err(Diagnostic::new(
+                        format!("redefinition of parameter `{}`", id.src),
+                        id.span,
+                    ));
+                }
+
+                parameters.push((id, r#type));
+            }
+            _ => return Err(Diagnostic::new("unsupported parameter", param.span)),
+        }
+    }
+
+    // Functions can only call those defined before them, so the only
+    // way to end up back here is calling itself.
+    let dispatched = block_calls(&funcdef.body, &mut |call| {
+        call.function.src == name.src
+            || env
+                .root()
+                .functions
+                .get(&call.function.src)
+                .is_some_and(|function| function.dispatched)
+    });
+
+    let return_type = ValueType::from_type_specifier(&funcdef.r#type, env)?;
+
+    if let ValueType::Array(..) = return_type {
+        return Err(Diagnostic::new(
+            format!("`{}` cannot return an array", name.src),
+            funcdef.r#type.span(),
+        ));
+    }
+
+    if Rail::of(&return_type).is_some() {
+        return Err(Diagnostic::new(
+            format!(
+                "`{}` cannot return `{return_type}`, which holds an array",
+                name.src
+            ),
+            funcdef.r#type.span(),
+        ));
+    }
+
+    Ok(Function {
+        name,
+        definition: funcdef,
+        return_type,
+        parameters,
+        dispatched,
+    })
+}
+
+/// Returns whether `stmt` contains a `return` statement, in which
+/// case whatever comes after it might have to be skipped.
+fn may_return(stmt: &Statement) -> bool {
+    match *stmt {
+        Statement::CaseStatement(ref cs) => cs
+            .children
+            .iter()
+            .any(|child| matches!(*child, BlockChild::Statement(ref stmt) if may_return(stmt))),
+        Statement::CompoundStatement(ref cs) => block_may_return(cs),
+        Statement::DoStatement(ref ds) => may_return(&ds.body),
+        Statement::ExpressionStatement(_) => false,
+        Statement::ForStatement(ref fs) => may_return(&fs.body),
+        Statement::GotoStatement(_) => false,
+        Statement::IfStatement(ref is) => {
+            may_return(&is.consequence)
+                || is
+                    .alternative
+                    .as_ref()
+                    .is_some_and(|alternative| may_return(&alternative.child))
+        }
+        Statement::LabeledStatement(ref ls) => {
+            matches!(*ls.child, BlockChild::Statement(ref stmt) if may_return(stmt))
+        }
+        Statement::ReturnStatement(_) => true,
+        Statement::SwitchStatement(ref ss) => block_may_return(&ss.body),
+        Statement::WhileStatement(ref ws) => may_return(&ws.body),
+        Statement::BreakStatement(_) | Statement::ContinueStatement(_) => false,
+    }
+}
+
+/// Returns whether `stmt` contains a `break` or `continue` statement
+/// of the loop (or `switch`) it's in, rather than of one within it, or
+/// a `goto` statement, in which case whatever comes after it in there
+/// might have to be skipped.
+fn may_jump(stmt: &Statement) -> bool {
+    jumps(stmt, true, true)
+}
+
+/// Returns whether `stmt` contains a `goto` statement.
+fn may_goto(stmt: &Statement) -> bool {
+    jumps(stmt, false, false)
+}
+
+/// Returns whether `stmt` contains a `goto` statement, or a `break`
+/// statement (if `breaks`) or `continue` statement (if `continues`) of
+/// the loop (or `switch`) it's in.
+fn jumps(stmt: &Statement, breaks: bool, continues: bool) -> bool {
+    let any = |children: &[BlockChild], breaks, continues| {
+        children.iter().any(|child| {
+            matches!(*child, BlockChild::Statement(ref stmt) if jumps(stmt, breaks, continues))
+        })
+    };
+
+    match *stmt {
+        Statement::CaseStatement(ref cs) => any(&cs.children, breaks, continues),
+        Statement::CompoundStatement(ref cs) => any(&cs.children, breaks, continues),
+        Statement::IfStatement(ref is) => {
+            jumps(&is.consequence, breaks, continues)
+                || is
+                    .alternative
+                    .as_ref()
+                    .is_some_and(|alternative| jumps(&alternative.child, breaks, continues))
+        }
+        Statement::LabeledStatement(ref ls) => any(slice::from_ref(&*ls.child), breaks, continues),
+        // only a `continue` (or `goto`) gets out of a `switch`
+        Statement::SwitchStatement(ref ss) => any(&ss.body.children, false, continues),
+        // and only a `goto` gets out of a loop
+        Statement::DoStatement(ref ds) => jumps(&ds.body, false, false),
+        Statement::ForStatement(ref fs) => jumps(&fs.body, false, false),
+        Statement::WhileStatement(ref ws) => jumps(&ws.body, false, false),
+        Statement::BreakStatement(_) => breaks,
+        Statement::ContinueStatement(_) => continues,
+        Statement::GotoStatement(_) => true,
+        Statement::ExpressionStatement(_) | Statement::ReturnStatement(_) => false,
+    }
+}
+
+/// Returns the labels (i.e., `case` statements) directly within the
+/// body of a `switch`, in order.
+fn switch_labels(node: &SwitchStatement) -> impl Iterator<Item = &CaseStatement> {
+    node.body.children.iter().filter_map(|child| match *child {
+        BlockChild::Statement(ref stmt) => match **stmt {
+            Statement::CaseStatement(ref cs) => Some(&**cs),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Returns the cell a loop with `body` (occurring within `env`)
+/// has to check on top of its condition, which is the running
+/// flag of the function if a `return` could stop the loop.
+fn loop_gate(body: &Statement, env: &Environment) -> Option<usize> {
+    if may_return(body) {
+        env.frame().and_then(|frame| frame.running())
+    } else {
+        None
+    }
+}
+
+/// Returns whether any statement within a block may return.
+fn block_may_return(block: &CompoundStatement) -> bool {
+    block
+        .children
+        .iter()
+        .any(|child| matches!(*child, BlockChild::Statement(ref stmt) if may_return(stmt)))
+}
+
 impl<'a> Environment<'a> {
-    /// Creates a new environment with an optional parent.
-    fn new(parent: Option<&'a Environment>) -> Self {
+    /// Creates a new environment with an optional parent, whose
+    /// locals begin at `stack_base`.
+    ///
+    /// The base is wherever the stack top is upon entering the
+    /// scope, which isn't necessarily just past the parent's locals
+    /// (e.g., an `if`'s flag can sit in between).
+    fn new(parent: Option<&'a Environment>, stack_base: usize) -> Self {
         Self {
             parent,
-            // horrendous (-ly beautiful?) one liner
-            stack_base: parent.map_or(0, |parent| {
-                parent.stack_base
-                    + parent
-                        .variables
-                        .values()
-                        .fold(0, |acc, &(_loc, r#type)| acc + r#type.size())
-            }),
+            stack_base,
             variables: HashMap::new(),
+            globals: HashMap::new(),
+            globals_base: None,
             types: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashSet::new(),
+            constants: HashMap::new(),
+            specifiers: HashSet::new(),
+            functions: HashMap::new(),
+            frame: None,
+            r#loop: None,
+            goto: None,
+        }
+    }
+
+    /// Returns the top-level environment, which `self` is
+    /// (possibly indirectly) a child of.
+    fn root(&self) -> &Environment<'a> {
+        match self.parent {
+            Some(parent) => parent.root(),
+            None => self,
         }
     }
 
-    /// Returns absolute location and type of a variable.
+    /// Returns absolute location and type of a variable, unless it's
+    /// shadowed by an enumerator.
     fn lookup_variable(&self, name: &str) -> Option<(usize, ValueType)> {
-        self.variables
-            .get(name)
-            .copied()
-            .or(self.parent.and_then(|parent| parent.lookup_variable(name)))
+        self.lookup_variable_in(name, self.globals_base())
+    }
+
+    /// Like `lookup_variable`, but with the globals region (as seen
+    /// from where the lookup began) at `globals_base`.
+    fn lookup_variable_in(&self, name: &str, globals_base: usize) -> Option<(usize, ValueType)> {
+        if self.constants.contains_key(name) {
+            return None;
+        }
+
+        if let Some((location, r#type)) = self.globals.get(name) {
+            return Some((globals_base + location, r#type.clone()));
+        }
+
+        self.variables.get(name).cloned().or_else(|| {
+            self.parent
+                .and_then(|parent| parent.lookup_variable_in(name, globals_base))
+        })
+    }
+
+    /// Returns absolute location and type of the variable that
+    /// `id` refers to, complaining if there is no such variable.
+    fn variable(&self, id: &Identifier) -> CodegenResult<(usize, ValueType)> {
+        self.lookup_variable(&id.src).ok_or_else(|| {
+            if self.lookup_constant(&id.src).is_some() {
+                Diagnostic::new(
+                    format!("`{}` is an enumerator, not a variable", id.src),
+                    id.span,
+                )
+            } else {
+                Diagnostic::new(format!("use of undeclared variable `{}`", id.src), id.span)
+            }
+        })
+    }
+
+    /// Returns the function that `id` refers to, complaining if
+    /// there is no such function.
+    fn function(&self, id: &Identifier) -> CodegenResult<&Function<'a>> {
+        self.root()
+            .functions
+            .get(&id.src)
+            .ok_or_else(|| unknown_function(id))
+    }
+
+    /// Returns the frame of the function call that `self` is
+    /// within, if any.
+    fn frame(&self) -> Option<&Frame> {
+        self.frame
+            .as_ref()
+            .or_else(|| self.parent.and_then(|parent| parent.frame()))
+    }
+
+    /// Returns the loops (and `switch`es) that `self` is within, from
+    /// the innermost out.
+    fn loops(&self) -> impl Iterator<Item = Loop> {
+        iter::successors(Some(self), |env| env.parent).filter_map(|env| env.r#loop)
+    }
+
+    /// Returns the innermost loop (or `switch`) that `self` is within,
+    /// if any.
+    fn innermost_loop(&self) -> Option<Loop> {
+        self.loops().next()
+    }
+
+    /// Returns the labels of the function body that `self` is within,
+    /// if it has any.
+    fn goto(&self) -> Option<&Goto> {
+        self.goto
+            .as_ref()
+            .or_else(|| self.parent.and_then(|parent| parent.goto()))
+    }
+
+    /// Returns the cells that stop whatever comes after a `return` or
+    /// `goto` once cleared, which are those of every loop (and `switch`)
+    /// that `self` is within and that of its function body's labels.
+    fn flags(&self) -> impl Iterator<Item = usize> {
+        let matched = match self.goto() {
+            Some(&Goto::Gated { matched, .. }) => Some(matched),
+            Some(Goto::Dispatched { .. }) | None => None,
+        };
+
+        self.loops().flat_map(Loop::flags).chain(matched)
+    }
+
+    /// Returns the cell that gates whatever comes after a statement
+    /// that may `return`, `break`, `continue` or `goto`, if there is
+    /// one. That's the innermost loop's (which a `return` clears too)
+    /// if it has one, or else that of the function body's labels, or
+    /// else the function's.
+    fn jump_gate(&self) -> Option<usize> {
+        match (self.innermost_loop(), self.goto()) {
+            (Some(Loop::Gated { iterating, .. }), _) => Some(iterating),
+            (Some(Loop::Switch { matched }), _) => Some(matched),
+            (Some(Loop::Dispatched { .. } | Loop::DispatchedSwitch { .. }), _) => None,
+            (None, Some(&Goto::Gated { matched, .. })) => Some(matched),
+            (None, Some(Goto::Dispatched { .. })) => None,
+            (None, None) => self.frame().and_then(|frame| frame.running()),
+        }
     }
 
     /// Returns type associated with a name.
     fn lookup_type(&self, name: &str) -> Option<ValueType> {
         self.types
             .get(name)
-            .copied()
-            .or(self.parent.and_then(|parent| parent.lookup_type(name)))
+            .cloned()
+            .or_else(|| self.parent.and_then(|parent| parent.lookup_type(name)))
     }
 
-    /// Adds a `typedef` type to the environment, given its name and type panicking if it redefines a type that already exists in the current scope.
-    fn add_type(&mut self, name: String, r#type: ValueType) {
-        if let Some(previous_type) = self.types.insert(name.clone(), r#type)
+    /// Adds a `typedef` type to the environment, given its name and type, failing if it redefines a type that already exists in the current scope.
+    fn add_type(&mut self, name: &TypeIdentifier, r#type: ValueType) -> CodegenResult {
+        if let Some(previous_type) = self.types.insert(name.src.clone(), r#type.clone())
             && previous_type != r#type
         {
-            panic!("redefined {} type", &name);
+            return Err(Diagnostic::new(
+                format!(
+                    "conflicting types for `{}` (`{previous_type}` and `{type}`)",
+                    name.src
+                ),
+                name.span,
+            ));
         }
+
+        Ok(())
     }
 
     /// Adds a `typedef` type to the environment given its definition node.
-    fn add_type_from_node(&mut self, typedef: &TypeDefinition) {
-        let (name, r#type) = interpret_type_definition(typedef, self);
+    fn add_type_from_node(&mut self, typedef: &TypeDefinition) -> CodegenResult {
+        self.add_tag_from_specifier(&typedef.r#type)?;
+
+        let (name, r#type) = interpret_type_definition(typedef, self)?;
+
+        self.add_type(name, r#type)
+    }
 
-        self.add_type(name, r#type);
+    /// Adds the `struct` or `enum` that a type specifier defines (if
+    /// it defines one) to the environment, unless it was added already.
+    fn add_tag_from_specifier(&mut self, spec: &TypeSpecifier) -> CodegenResult {
+        if !self.specifiers.insert(spec.span().start_byte) {
+            return Ok(());
+        }
+
+        match *spec {
+            TypeSpecifier::StructSpecifier(ref spec) => self.add_struct(spec),
+            TypeSpecifier::EnumSpecifier(ref spec) => self.add_enum(spec),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -223,6 +1406,9 @@ impl Codegen {
             src: src.to_string(),
             stack_pointer: 0,
             output: String::new(),
+            diagnostics: Vec::new(),
+            dispatch: Dispatch::default(),
+            globals: Globals::default(),
         }
     }
 
@@ -281,118 +1467,382 @@ impl Codegen {
         }
     }
 
-    /// Generates a BF loop where the code in `blk` is executed in-between pushing the loop's delimiting `[` and `]`. The closure `blk` must accept a mutable reference to the `Codegen` object which it then uses to invokes any code generation.
-    fn bf_loop<F: FnOnce(&mut Self)>(&mut self, blk: F) {
-        self.push('[');
-        blk(self);
-        self.push(']');
+    /// Moves the memory head to the absolute location `location`.
+    fn move_to(&mut self, location: usize) {
+        self.move_head(location.cast_signed() - self.stack_pointer.cast_signed());
     }
 
-    /// Clears the all contents of `env`'s local variables,
-    /// resetting the codegen's stack pointer to base as well.
-    fn clear_environment(&mut self, env: Environment) {
-        let locals_size = self.stack_pointer - env.stack_base;
+    /// Moves the `size`-cell value at `source` onto the values at
+    /// each of `destinations` (adding to whatever they hold),
+    /// clearing `source`.
+    fn move_value(&mut self, source: usize, destinations: &[usize], size: usize) {
+        for i in 0..size {
+            self.move_to(source + i);
+            self.bf_loop(|cg| {
+                cg.push('-');
 
-        self.push_n_str(locals_size, "<[-]");
+                for &destination in destinations {
+                    cg.move_to(destination + i);
+                    cg.push('+');
+                }
 
-        self.stack_pointer = env.stack_base;
+                cg.move_to(source + i);
+            });
+        }
     }
 
-    /// Adds `declaration` to the environment, reserving space for
-    /// it and adding it to `env.variables`.
-    ///
-    /// Assumes the stack pointer is at the appropriate location
-    /// to insert the variable.
-    fn add_variable(&mut self, env: &mut Environment, decl: &Declaration) {
-        let (name, r#type) = interpret_declaration(decl, env);
-
-        if env.variables.contains_key(&name) {
-            panic!("Colliding variable declaration");
-        }
+    /// Copies the `size`-cell value at `source` onto the value at
+    /// `destination`, using `top` (and what follows) as a temporary.
+    fn copy_value(&mut self, source: usize, destination: usize, size: usize, top: usize) {
+        self.move_value(source, &[destination, top], size);
+        self.move_value(top, &[source], size);
+    }
 
-        env.variables.insert(name, (self.stack_pointer, r#type));
+    /// Converts the value of type `from` on top of the stack into
+    /// type `to`, extending (with the sign bit for signed types, or
+    /// zeroes otherwise) or truncating integers as needed.
+    fn convert(&mut self, from: ValueType, to: ValueType) {
+        let (from_size, to_size) = (from.size(), to.size());
 
-        self.move_head(r#type.size().cast_signed());
-    }
+        if to_size > from_size && from.is_signed() {
+            let extension = self.stack_pointer;
+            let flag = extension + to_size - from_size;
 
-    /// Top-level call to compile the C file to BF.
-    pub fn generate(mut self) -> String {
-        let translation_unit = parse(self.src.as_str());
+            // negative values get every new cell set to 255
+            self.sign_bit(extension - 1, flag, flag + 1);
+            self.move_to(flag);
+            self.bf_loop(|cg| {
+                cg.push('-');
 
-        self.translation_unit(&translation_unit);
+                for cell in extension..flag {
+                    cg.move_to(cell);
+                    cg.push('-');
+                }
 
-        self.output
+                cg.move_to(flag);
+            });
+        } else if to_size > from_size {
+            self.move_head((to_size - from_size).cast_signed());
+        } else {
+            for _ in to_size..from_size {
+                self.move_head(-1);
+                self.push_str("[-]");
+            }
+        }
     }
 
-    /// Generate code for a `translation_unit` node.
-    ///
+    /// Generates a BF loop where the code in `blk` is executed in-between pushing the loop's delimiting `[` and `]`. The closure `blk` must accept a mutable reference to the `Codegen` object which it then uses to invokes any code generation. Whatever `blk` returns (e.g., a `CodegenResult`) is passed along.
+    fn bf_loop<T, F: FnOnce(&mut Self) -> T>(&mut self, blk: F) -> T {
+        self.push('[');
+        let result = blk(self);
+        self.push(']');
+
+        result
+    }
+
+    /// Generates the code from `blk` such that it only runs if the
+    /// cell at `flag` (if any) is nonzero, using the stack top as
+    /// a temporary. `blk` must leave the stack as it found it.
+    fn gate<F: FnOnce(&mut Self) -> CodegenResult>(
+        &mut self,
+        flag: Option<usize>,
+        blk: F,
+    ) -> CodegenResult {
+        let Some(flag) = flag else {
+            return blk(self);
+        };
+
+        let temp = self.stack_pointer;
+        self.copy_value(flag, temp, 1, temp + 1);
+        self.move_to(temp);
+
+        self.bf_loop(|cg| {
+            cg.push_str("[-]");
+            cg.move_head(1);
+
+            let result = blk(cg);
+            cg.move_to(temp);
+
+            result
+        })
+    }
+
+    /// Records the diagnostic of a failed `result`, moving the
+    /// stack pointer back to `stack_top` (where it would've been
+    /// had the failed code generated normally) so that generation
+    /// can carry on.
+    fn recover(&mut self, result: CodegenResult, stack_top: usize) {
+        if let Err(diagnostic) = result {
+            self.diagnostics.push(diagnostic);
+            self.stack_pointer = stack_top;
+        }
+    }
+
+    /// Clears the all contents of `env`'s local variables,
+    /// resetting the codegen's stack pointer to base as well.
+    fn clear_environment(&mut self, env: Environment) {
+        let locals_size = self.stack_pointer - env.stack_base;
+
+        self.push_n_str(locals_size, "<[-]");
+
+        self.stack_pointer = env.stack_base;
+    }
+
+    /// Adds `declaration` to the environment, reserving space for
+    /// it (and its rail, if it holds arrays) and adding it to
+    /// `env.variables`. Globals and `static` locals go in the
+    /// globals region instead (see `add_global`).
+    ///
+    /// Assumes the stack pointer is at the appropriate location
+    /// to insert the variable.
+    fn add_variable(&mut self, env: &mut Environment, decl: &Declaration) -> CodegenResult {
+        env.add_tag_from_specifier(&decl.r#type)?;
+
+        let (name, r#type) = interpret_declaration(decl, env)?;
+
+        if env.variables.contains_key(&name.src)
+            || env.globals.contains_key(&name.src)
+            || env.constants.contains_key(&name.src)
+        {
+            return Err(Diagnostic::new(
+                format!("redeclaration of `{}`", name.src),
+                name.span,
+            ));
+        }
+
+        if is_static(decl)? || env.parent.is_none() {
+            return self.add_global(env, decl, name, r#type);
+        }
+
+        if let Some(rail) = Rail::of(&r#type) {
+            self.move_head(rail.size().cast_signed());
+        }
+
+        let size = r#type.size();
+        env.variables
+            .insert(name.src.clone(), (self.stack_pointer, r#type));
+
+        self.move_head(size.cast_signed());
+
+        Ok(())
+    }
+
+    /// Top-level call to compile the C file to BF, returning
+    /// every problem found if it couldn't be compiled.
+    pub fn generate(mut self) -> Result<String, Vec<Diagnostic>> {
+        let translation_unit = parse(self.src.as_str()).map_err(|error| {
+            error
+                .issues
+                .into_iter()
+                .map(|(issue, span)| Diagnostic::new(issue.to_string(), span))
+                .collect::<Vec<_>>()
+        })?;
+
+        self.lay_out_globals(&translation_unit);
+        self.translation_unit(&translation_unit);
+
+        if self.diagnostics.is_empty() {
+            Ok(self.output)
+        } else {
+            Err(self.diagnostics)
+        }
+    }
+
+    /// Generate code for a `translation_unit` node.
+    ///
     /// For the purposes of this project this refers to
     /// a parsed C file.
     fn translation_unit(&mut self, root: &TranslationUnit) {
-        let mut top_level_env = Environment::new(None);
+        // the globals come first
+        let stack_base = self.globals.size();
+        self.stack_pointer = stack_base;
+
+        let mut top_level_env = Environment::new(None, stack_base);
 
         for child in &root.children {
-            match *child {
-                TUChildren::FunctionDefinition(ref funcdef) => match *funcdef.declarator {
-                    Declarator::FunctionDeclarator(ref fd) => {
-                        if let Declarator::Identifier(ref func_name) = *fd.declarator
-                            && func_name.src == "main"
-                        {
-                            match *funcdef.r#type {
-                                TypeSpecifier::PrimitiveType(ref t) if t.src.as_str() == "int" => {}
-                                _ => panic!("main function does not have `int` return type"),
-                            }
-
-                            // sorry it's so unprofessional i just wanted the compiler to shut up
-                            fd.parameters.children.iter().for_each(|param_decl| {
-                                match *param_decl.declarator {
-                                    Declarator::Identifier(ref id) => println!("{} dies", &id.src),
-                                    Declarator::FunctionDeclarator(_) => println!("do NOT even bother passing a function declarator as a function parameter"),
-                                    Declarator::InitDeclarator(_) => println!("initialization??? in THIS signature??"),
-                                }
-                                match *param_decl.r#type {
-                                    TypeSpecifier::PrimitiveType(ref t) => println!("ESPECIALLY if it's of type {}", &t.src),
-                                    TypeSpecifier::TypeIdentifier(ref id) => println!("even with a type of {}", &id.src),
-                                }
-                                panic!("no chance main has any parameters");
-                            });
-
-                            self.main(funcdef, &top_level_env)
-                        } else {
-                            unimplemented!("non-main functions");
-                        }
-                    }
-                    Declarator::Identifier(_) => unimplemented!(),
-                    Declarator::InitDeclarator(_) => unimplemented!(),
-                },
+            let stack_top = self.stack_pointer;
+
+            let result = match *child {
+                TUChildren::Declaration(ref decl) => self.add_variable(&mut top_level_env, decl),
+                TUChildren::FunctionDefinition(ref funcdef) => {
+                    self.function_definition(funcdef, &mut top_level_env)
+                }
                 TUChildren::TypeDefinition(ref typedef) => {
                     top_level_env.add_type_from_node(typedef)
                 }
-            }
+                TUChildren::StructSpecifier(ref spec) => top_level_env.add_struct(spec),
+                TUChildren::EnumSpecifier(ref spec) => top_level_env.add_enum(spec),
+            };
+
+            self.recover(result, stack_top);
+        }
+
+        self.globals_prelude(stack_base);
+    }
+
+    /// Adds a function definition to `env` so it can be called,
+    /// generating it right away if it's `main`.
+    ///
+    /// Other functions only get generated where they're called (or
+    /// along with `main`, if dispatched), so their bodies are generated
+    /// once here (then thrown away) just to report any problems
+    /// within them.
+    fn function_definition<'a>(
+        &mut self,
+        funcdef: &'a FunctionDefinition,
+        env: &mut Environment<'a>,
+    ) -> CodegenResult {
+        let function = interpret_function_definition(funcdef, env)?;
+        let name = function.name;
+
+        if Builtin::from_name(&name.src).is_some() || env.functions.contains_key(&name.src) {
+            return Err(Diagnostic::new(
+                format!("redefinition of `{}`", name.src),
+                name.span,
+            ));
+        }
+
+        env.functions.insert(name.src.clone(), function);
+        let function = &env.functions[&name.src];
+
+        if name.src == "main" {
+            return self.main(function, env);
         }
+
+        if function.dispatched {
+            return self.check_dispatched_function(function, env);
+        }
+
+        let (output_len, stack_top) = (self.output.len(), self.stack_pointer);
+
+        // parameters are left zeroed
+        let result = self.stack_pointer;
+        self.move_head(function.return_type.size().cast_signed());
+        let result = self.function_body(function, result, env, |cg| {
+            for (_, r#type) in &function.parameters {
+                cg.move_head(r#type.size().cast_signed());
+            }
+
+            Ok(())
+        });
+
+        self.output.truncate(output_len);
+        self.stack_pointer = stack_top;
+
+        result
     }
 
     /// Generate code for the `main` function, which is
     /// where program execution begins.
-    fn main(&mut self, function: &FunctionDefinition, env: &Environment) {
-        self.compound_statement(function.body.as_ref(), env);
+    fn main(&mut self, function: &Function, env: &Environment) -> CodegenResult {
+        if function.return_type != ValueType::Int {
+            return Err(Diagnostic::new(
+                "`main` function must have `int` return type",
+                function.definition.r#type.span(),
+            ));
+        }
+
+        if let Some(&(param, ref r#type)) = function.parameters.first() {
+            return Err(Diagnostic::new(
+                format!(
+                    "`main` function cannot take parameters (found `{type} {}`)",
+                    param.src
+                ),
+                param.span,
+            ));
+        }
+
+        if function.dispatched {
+            return self.dispatched_main(function, env);
+        }
+
+        // the return value only needs space if there is one
+        let result = self.stack_pointer;
+        let result_size = if block_may_return(&function.definition.body) {
+            ValueType::Int.size()
+        } else {
+            0
+        };
+        self.move_head(result_size.cast_signed());
+
+        self.function_body(function, result, env, |_| Ok(()))?;
+
+        // nothing to hand the return value to
+        self.push_n_str(result_size, "<[-]");
+        self.stack_pointer -= result_size;
+
+        Ok(())
+    }
+
+    /// Generates the body of `function` in a new frame on top of the
+    /// stack, returning into the space at `result`. The parameters
+    /// take their values from whatever `push_arguments` pushes.
+    ///
+    /// Leaves only the return value behind.
+    fn function_body(
+        &mut self,
+        function: &Function,
+        result: usize,
+        root: &Environment,
+        push_arguments: impl FnOnce(&mut Self) -> CodegenResult,
+    ) -> CodegenResult {
+        let body = &function.definition.body;
+        let mut frame_env = Environment::new(Some(root), self.stack_pointer);
+
+        if block_may_return(body) {
+            frame_env.frame = Some(Frame {
+                result,
+                return_type: function.return_type.clone(),
+                exit: Return::Gated {
+                    running: self.stack_pointer,
+                },
+            });
+
+            self.push('+');
+            self.move_head(1);
+        }
+
+        let mut location = self.stack_pointer;
+        push_arguments(self)?;
+
+        for &(param, ref r#type) in &function.parameters {
+            frame_env
+                .variables
+                .insert(param.src.clone(), (location, r#type.clone()));
+            location += r#type.size();
+        }
+
+        self.function_block(body, &frame_env)?;
+
+        self.clear_environment(frame_env);
+
+        Ok(())
     }
 
     /// This generates code for a scoping block (known internally
     /// as a compound statement). Creates a new environment for
     /// the local variables and types declared here.
-    fn compound_statement(&mut self, node: &CompoundStatement, parent_env: &Environment) {
-        let mut env = Environment::new(Some(parent_env));
+    ///
+    /// Problems within the block's children are recorded rather
+    /// than returned, so this never fails itself.
+    fn compound_statement(
+        &mut self,
+        node: &CompoundStatement,
+        parent_env: &Environment,
+    ) -> CodegenResult {
+        let mut env = Environment::new(Some(parent_env), self.stack_pointer);
+
+        // Once a child may have returned, the ones after it
+        // only run while the function is still running.
+        let mut gate = None;
 
         for child in &node.children {
-            match *child {
-                BlockChild::Declaration(ref decl) => {
-                    // like why both...
-                    self.add_variable(&mut env, decl);
-                    self.declaration(decl, &env);
-                }
-                BlockChild::Statement(ref stmt) => self.statement(stmt, &env),
-                BlockChild::TypeDefinition(ref typedef) => env.add_type_from_node(typedef),
+            self.block_child(child, &mut env, gate);
+
+            if let BlockChild::Statement(ref stmt) = *child
+                && (may_return(stmt) || may_jump(stmt))
+            {
+                gate = env.jump_gate();
             }
         }
 
@@ -410,120 +1860,205 @@ impl Codegen {
         );
 
         self.clear_environment(env);
+
+        Ok(())
+    }
+
+    /// Generates code for a child of a block, whose environment (`env`)
+    /// gets any declaration added to it. Unless it's a declaration
+    /// without an initializer, the child only runs if the cell at
+    /// `gate` (if any) is nonzero.
+    ///
+    /// Problems are recorded rather than returned.
+    fn block_child(&mut self, child: &BlockChild, env: &mut Environment, gate: Option<usize>) {
+        let stack_top = self.stack_pointer;
+
+        let result = match *child {
+            BlockChild::Declaration(ref decl) => {
+                // like why both...
+                match self.add_variable(env, decl) {
+                    Ok(()) => {
+                        let stack_top = self.stack_pointer;
+                        let result = self.gate(gate, |cg| cg.declaration(decl, env));
+
+                        self.recover(result, stack_top);
+                        Ok(())
+                    }
+                    err => err,
+                }
+            }
+            BlockChild::Statement(ref stmt) => self.gate(gate, |cg| cg.statement(stmt, env)),
+            BlockChild::TypeDefinition(ref typedef) => env.add_type_from_node(typedef),
+            BlockChild::StructSpecifier(ref spec) => env.add_struct(spec),
+            BlockChild::EnumSpecifier(ref spec) => env.add_enum(spec),
+        };
+
+        self.recover(result, stack_top);
     }
 
     /// Generates code for a variable declaration, assuming
     /// the environment already has an assigned location for it.
     // TODO: Merge this and `add_variable`, they feel like they should just be the same thing.
-    fn declaration(&mut self, decl: &Declaration, env: &Environment<'_>) {
+    fn declaration(&mut self, decl: &Declaration, env: &Environment<'_>) -> CodegenResult {
+        // those were initialized along with the globals
+        if is_static(decl)? {
+            return Ok(());
+        }
+
         match *decl.declarator {
             Declarator::Identifier(_) => {}
             Declarator::InitDeclarator(ref init) => {
-                let (name, r#type) = interpret_declaration(decl, env);
+                let (name, r#type) = interpret_declaration(decl, env)?;
 
-                // TODO: type casting?
-                let expr_type = ValueType::from_expression(&init.value, env);
-                if r#type != expr_type {
-                    unimplemented!("Assigning values of incompatible types");
-                }
-                let var_size = r#type.size();
+                // discarding type because we already established it from `interpret_declaration`.
+                // i really should merge these functions but wtv
+                let var_location = env.variables[&name.src].0;
 
-                // push and do NOT inspect
-                self.expression(&init.value, env);
+                self.initialize(init, name, &r#type, var_location, env)?;
+            }
+            Declarator::ArrayDeclarator(_) => {}
+            Declarator::FunctionDeclarator(_)
+            | Declarator::TypeIdentifier(_)
+            | Declarator::FieldIdentifier(_) => {
+                unreachable!("rejected by `add_variable`")
+            }
+        }
 
-                // now pointer is directly after data, e.g.:
-                // xxx...yyy
-                //          ^
-                // size = 3
-                // var_offset = 6
+        Ok(())
+    }
 
-                // discarding type because we already established it from `interpret_declaration`.
-                // i really should merge these functions but wtv
-                let (var_location, _) = env.variables[&name];
-                let var_offset = self.stack_pointer - var_size - var_location;
-
-                // for each cell...
-                for _ in 0..var_size {
-                    // move into the cell
-                    self.move_head(-1);
-                    // while cell isn't empty...
-                    self.bf_loop(|cg| {
-                        // decrement from stack cell
-                        cg.push('-');
-                        // move to corresponding variable cell
-                        cg.push_n(var_offset, '<');
-                        // increment variable cell
-                        cg.push('+');
-                        // move back to stack cell
-                        cg.push_n(var_offset, '>');
-                    });
-                }
-                // now we're AT stack empty so we're chill.
+    /// Generates code giving the variable `name` (of type `r#type`,
+    /// at `var_location`) the value of its initializer.
+    fn initialize(
+        &mut self,
+        init: &InitDeclarator,
+        name: &Identifier,
+        r#type: &ValueType,
+        var_location: usize,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let var_size = r#type.size();
+
+        let value = match *init.value {
+            Initializer::InitializerList(ref list) => {
+                return self.initializer_list(list, r#type, var_location, env);
             }
-            Declarator::FunctionDeclarator(_) => unimplemented!(),
+            Initializer::Expression(ref value) => value,
+        };
+
+        if let ValueType::Array(..) = *r#type {
+            if let Expression::StringLiteral(ref sl) = **value {
+                return self.string_initializer(sl, r#type, var_location);
+            }
+
+            return Err(Diagnostic::new(
+                format!(
+                    "array `{}` must be initialized with an initializer list",
+                    name.src
+                ),
+                value.span(),
+            ));
         }
+
+        // push and do NOT inspect
+        let value_location = self.stack_pointer;
+        self.expression_as(value, r#type.clone(), env)?;
+
+        self.move_value(value_location, &[var_location], var_size);
+        self.move_to(value_location);
+        // now we're AT stack empty so we're chill.
+
+        Ok(())
     }
 
     /// Generates code for any statement.
-    fn statement(&mut self, stmt: &Statement, env: &Environment<'_>) {
+    fn statement(&mut self, stmt: &Statement, env: &Environment<'_>) -> CodegenResult {
         match *stmt {
             Statement::CompoundStatement(ref cs) => self.compound_statement(cs, env),
-            Statement::ExpressionStatement(ref es) => {
-                let child = &es.child;
-
-                let old_stack_top = self.stack_pointer;
-
-                self.expression(child, env);
-
-                let clear_zone_size = self.stack_pointer - old_stack_top;
-                for _ in 0..clear_zone_size {
-                    self.move_head(-1);
-                    self.push_str("[-]");
-                }
+            // in dispatched functions, these might need splitting into blocks
+            Statement::DoStatement(ref ds) if splits(stmt, env) => {
+                self.dispatched_do_statement(ds, env)
+            }
+            Statement::DoStatement(ref ds) => self.do_statement(ds, env),
+            Statement::ExpressionStatement(ref es) => es
+                .children
+                .iter()
+                .try_for_each(|expr| self.discard(expr, env)),
+            Statement::ForStatement(ref fs) if splits(stmt, env) => {
+                self.dispatched_for_statement(fs, env)
             }
             Statement::ForStatement(ref fs) => self.for_statement(fs, env),
+            Statement::IfStatement(ref is) if splits(stmt, env) => {
+                self.dispatched_if_statement(is, env)
+            }
+            Statement::GotoStatement(ref gs) => self.goto_statement(gs, env),
             Statement::IfStatement(ref is) => self.if_statement(is, env),
+            // the ones directly within a function's body never get here
+            Statement::LabeledStatement(ref ls) => Err(Diagnostic::new(
+                format!(
+                    "label `{}` must be directly within the body of its function",
+                    ls.label.src
+                ),
+                ls.label.span,
+            )),
+            Statement::ReturnStatement(ref rs) => self.return_statement(rs, env),
+            Statement::SwitchStatement(ref ss) if splits(stmt, env) => {
+                self.dispatched_switch_statement(ss, env)
+            }
+            Statement::SwitchStatement(ref ss) => self.switch_statement(ss, env),
+            Statement::CaseStatement(ref cs) => Err(Diagnostic::new(
+                format!(
+                    "`{}` label not directly within a `switch`",
+                    if cs.value.is_some() {
+                        "case"
+                    } else {
+                        "default"
+                    }
+                ),
+                cs.span,
+            )),
+            Statement::WhileStatement(ref ws) if splits(stmt, env) => {
+                self.dispatched_while_statement(ws, env)
+            }
             Statement::WhileStatement(ref ws) => self.while_statement(ws, env),
+            Statement::BreakStatement(span) => self.jump_statement(span, true, env),
+            Statement::ContinueStatement(span) => self.jump_statement(span, false, env),
         }
     }
 
-    /// Generates code for a `for` statement.
-    fn for_statement(&mut self, node: &ForStatement, env: &Environment<'_>) {
-        // The environment wherein the for loop expressions/statements exist
-        let mut outer_env = Environment::new(Some(env));
+    /// Evaluates an expression only for its side effects, clearing
+    /// its value off the stack.
+    fn discard(&mut self, expr: &Expression, env: &Environment<'_>) -> CodegenResult {
+        let old_stack_top = self.stack_pointer;
 
-        if let Some(initializer) = &node.initializer {
-            match **initializer {
-                ForLoopInitializer::Declaration(ref d) => {
-                    self.add_variable(&mut outer_env, d);
+        self.expression(expr, env)?;
 
-                    self.declaration(d, &outer_env);
-                }
-                ForLoopInitializer::Expression(ref e) => {
-                    let old_sp = self.stack_pointer;
+        let clear_zone_size = self.stack_pointer - old_stack_top;
+        self.push_n_str(clear_zone_size, "<[-]");
+        self.stack_pointer = old_stack_top;
 
-                    self.expression(e, &outer_env);
+        Ok(())
+    }
 
-                    let dist = self.stack_pointer - old_sp;
-                    self.push_n_str(dist, "<[-]");
-                    self.stack_pointer -= dist;
-                }
-            }
-        }
+    /// Generates code for a `for` statement.
+    fn for_statement(&mut self, node: &ForStatement, env: &Environment<'_>) -> CodegenResult {
+        // The environment wherein the for loop expressions/statements exist
+        let mut outer_env = Environment::new(Some(env), self.stack_pointer);
+
+        self.for_initializer(node, &mut outer_env)?;
+
+        // a `return` or `break` in the body stops the loop
+        let (loop_env, running) = self.loop_environment(&node.body, &outer_env);
 
         // pushes condition then moves head back so it's examining it
-        let examine_condition = |cg: &mut Self| {
-            match node.condition {
-                Some(ref cond) => {
-                    cg.expression(cond, &outer_env);
-                    cg.move_head(-1);
-                }
-                // always true
-                None => cg.push('+'),
-            }
+        let examine_condition = |cg: &mut Self| -> CodegenResult {
+            cg.loop_condition(node.condition.as_deref(), running, &loop_env)?;
+            cg.move_head(-1);
+
+            Ok(())
         };
 
-        examine_condition(self);
+        examine_condition(self)?;
 
         self.bf_loop(|cg| {
             // clear cond if true
@@ -532,43 +2067,177 @@ impl Codegen {
             // common case is compound_statement;
             // in which case, new environment created,
             // which is correct behavior.
-            cg.statement(&node.body, &outer_env);
+            cg.loop_body(&node.body, &loop_env)?;
 
             if let Some(update) = &node.update {
-                let old_sp = cg.stack_pointer;
+                cg.gate(running, |cg| cg.discard(update, &loop_env))?;
+            }
+
+            examine_condition(cg)
+        })?;
+
+        self.clear_environment(loop_env);
+        self.clear_environment(outer_env);
 
-                cg.expression(update, &outer_env);
+        Ok(())
+    }
 
-                let dist = cg.stack_pointer - old_sp;
+    /// Generates the initializer of a `for` statement (if any), whose
+    /// variables go in `outer_env`.
+    fn for_initializer(
+        &mut self,
+        node: &ForStatement,
+        outer_env: &mut Environment<'_>,
+    ) -> CodegenResult {
+        for initializer in &node.initializer {
+            match *initializer {
+                ForLoopInitializer::Declaration(ref d) => {
+                    self.add_variable(outer_env, d)?;
 
-                // maybe new move_and_clear function? or would
-                // that tread too far into premature abstraction?
-                cg.push_n_str(dist, "<[-]");
-                cg.stack_pointer -= dist;
+                    self.declaration(d, outer_env)?;
+                }
+                ForLoopInitializer::Expression(ref e) => self.discard(e, outer_env)?,
             }
+        }
 
-            examine_condition(cg);
-        });
+        Ok(())
+    }
 
-        self.clear_environment(outer_env);
+    /// Fails unless `condition` is of a type that can be
+    /// used to decide control flow.
+    fn check_condition(condition: &Expression, env: &Environment) -> CodegenResult {
+        match ValueType::from_expression(condition, env)? {
+            ValueType::Bool => Ok(()),
+            r#type if r#type.is_integer() => Ok(()),
+            other => Err(Diagnostic::new(
+                format!("cannot use a value of type `{other}` as a condition"),
+                condition.span(),
+            )),
+        }
     }
 
-    /// Generates code for an `if` statement.
-    fn if_statement(&mut self, node: &IfStatement, env: &Environment<'_>) {
-        if !matches!(
-            ValueType::from_expression(&node.condition.child, env),
-            ValueType::Char | ValueType::Bool
-        ) {
-            unimplemented!("Condition of type other than bool or char");
+    /// Evaluates a condition and pushes a single cell onto stack
+    /// that's nonzero if and only if the condition holds.
+    fn condition(&mut self, condition: &Expression, env: &Environment<'_>) -> CodegenResult {
+        Self::check_condition(condition, env)?;
+
+        let location = self.stack_pointer;
+        let size = ValueType::from_expression(condition, env)?.size();
+
+        self.expression(condition, env)?;
+
+        if size > 1 {
+            self.collapse(location, size);
+            self.move_to(location + 1);
+        }
+
+        Ok(())
+    }
+
+    /// Generates code for a `return` statement, which moves the
+    /// return value (if any) into the frame and stops the function
+    /// from running any further (see `Return`).
+    fn return_statement(&mut self, node: &ReturnStatement, env: &Environment<'_>) -> CodegenResult {
+        let frame = env.frame().expect("functions that may return have a frame");
+
+        match (node.children.first(), frame.return_type.clone()) {
+            (None, ValueType::Void) => {}
+            (None, r#type) => {
+                return Err(Diagnostic::new(
+                    format!("missing return value of type `{type}`"),
+                    node.span,
+                ));
+            }
+            (Some(value), ValueType::Void) => {
+                return Err(Diagnostic::new(
+                    "cannot return a value from a `void` function",
+                    value.span(),
+                ));
+            }
+            (Some(value), r#type) => {
+                let value_location = self.stack_pointer;
+                let size = r#type.size();
+                self.expression_as(value, r#type, env)?;
+
+                self.move_value(value_location, &[frame.result], size);
+                self.move_to(value_location);
+            }
+        }
+
+        let top = self.stack_pointer;
+
+        match frame.exit {
+            Return::Gated { running } => {
+                self.move_to(running);
+                self.push_str("[-]");
+
+                // which stops any loops it's in too
+                for cell in env.flags() {
+                    self.move_to(cell);
+                    self.push_str("[-]");
+                }
+
+                self.move_to(top);
+            }
+            Return::Dispatched { ret, locals } => {
+                for cell in (locals..top).rev() {
+                    self.move_to(cell);
+                    self.push_str("[-]");
+                }
+
+                self.return_to_caller(ret, top);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pushes the condition of a loop as a single cell, which is 1
+    /// if there is no condition. With `running` (see `loop_gate`),
+    /// the condition only gets evaluated, and can only hold, while
+    /// the cell there is nonzero.
+    fn loop_condition(
+        &mut self,
+        condition: Option<&Expression>,
+        running: Option<usize>,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        if running.is_none() {
+            return match condition {
+                Some(condition) => self.condition(condition, env),
+                // always true
+                None => {
+                    self.push('+');
+                    self.move_head(1);
+
+                    Ok(())
+                }
+            };
         }
 
+        let cell = self.stack_pointer;
+        self.move_head(1);
+
+        self.gate(running, |cg| {
+            cg.loop_condition(condition, None, env)?;
+            cg.move_value(cell + 2, &[cell], 1);
+            cg.move_to(cell + 2);
+
+            Ok(())
+        })
+    }
+
+    /// Generates code for an `if` statement.
+    fn if_statement(&mut self, node: &IfStatement, env: &Environment<'_>) -> CodegenResult {
+        Self::check_condition(&node.condition.child, env)?;
+
         if let Some(alternative) = &node.alternative {
             // Init flag to 1
             self.push('+');
             self.move_head(1);
 
             // Examine condition
-            self.parenthesized_expression(&node.condition, env);
+            self.condition(&node.condition.child, env)?;
             self.move_head(-1);
 
             // If cond != 0 (true), set flag = 0, eval consequence
@@ -576,8 +2245,8 @@ impl Codegen {
                 cg.push_str("<->");
                 cg.push_str("[-]");
 
-                cg.statement(&node.consequence, env);
-            });
+                cg.statement(&node.consequence, env)
+            })?;
 
             // Cond space guaranteed to be zero, moving to examine flag
             self.move_head(-1);
@@ -586,414 +2255,1092 @@ impl Codegen {
             self.bf_loop(|cg| {
                 cg.push('-');
 
-                cg.statement(&alternative.child, env);
-            });
+                cg.statement(&alternative.child, env)
+            })
         } else {
             // Examine condition
-            self.parenthesized_expression(&node.condition, env);
+            self.condition(&node.condition.child, env)?;
             self.move_head(-1);
 
             // If cond != 0 (true), set it to zero and eval consequence
             self.bf_loop(|cg| {
                 cg.push_str("[-]");
 
-                cg.statement(&node.consequence, env);
-            });
+                cg.statement(&node.consequence, env)
+            })
         }
     }
 
-    /// Generates code for a `while` statement.
-    fn while_statement(&mut self, node: &WhileStatement, env: &Environment<'_>) {
-        if !matches!(
-            ValueType::from_expression(&node.condition.child, env),
-            ValueType::Char | ValueType::Bool
-        ) {
-            unimplemented!("Condition of type other than bool or char");
-        }
-
-        // Examine condition
-        self.parenthesized_expression(&node.condition, env);
-        self.push('<');
-        self.stack_pointer -= 1;
+    /// Generates code for a `switch` statement, which counts down the
+    /// position of the label it jumps to (see `switch_entry`) at every
+    /// label, setting the cell that gates everything within it once
+    /// that reaches zero. From then on, it falls through every label
+    /// until something gets out of it.
+    fn switch_statement(&mut self, node: &SwitchStatement, env: &Environment<'_>) -> CodegenResult {
+        let entry = self.stack_pointer;
+        self.switch_entry(node, env)?;
 
-        // If cond != 0, clear and evaluate body
-        self.bf_loop(|cg| {
-            cg.push_str("[-]");
+        let matched = self.stack_pointer;
+        self.move_head(1);
 
-            cg.statement(&node.body, env);
+        let mut switch_env = Environment::new(Some(env), entry);
+        switch_env.r#loop = Some(Loop::Switch { matched });
 
-            // Examine condition again so we can run it back
-            cg.parenthesized_expression(&node.condition, env);
-            cg.push('<');
-            cg.stack_pointer -= 1;
+        self.switch_body(node, &mut switch_env, Some(matched), |cg, _| {
+            cg.reach_label(entry, matched);
         });
-    }
 
-    /// Evaluates any expression and pushes its value onto stack.
-    fn expression(&mut self, expr: &Expression, env: &Environment<'_>) {
-        match *expr {
-            Expression::AssignmentExpression(ref ae) => self.assignment_expression(ae, env),
-            Expression::BinaryExpression(ref be) => self.binary_expression(&be, env),
-            Expression::CallExpression(ref ce) => {
-                self.argument_list(&ce.arguments, env);
-
-                match ce.function.src.as_str() {
-                    "putchar" => {
-                        self.move_head(-1);
-                        self.push_str(".[-]");
-                    }
-                    _ => unimplemented!("Non-putchar function invocation"),
-                }
-            }
-            Expression::CharLiteral(ref cl) => self.char_literal_expression(cl),
-            Expression::Identifier(ref id) => self.identifier(id, env),
-            Expression::NumberLiteral(ref nl) => {
-                let num = nl.src.parse::<usize>().unwrap();
+        self.clear_environment(switch_env);
 
-                self.push_n(num, '+');
-                self.move_head(1);
-            }
-            Expression::True => {
-                self.push('+');
-                self.move_head(1);
-            }
-            Expression::False => self.move_head(1),
-            Expression::UpdateExpression(ref update_expr) => {
-                self.update_expression(update_expr, env)
-            }
-            Expression::ParenthesizedExpression(ref paren_expr) => {
-                self.parenthesized_expression(paren_expr, env)
-            }
-        }
+        Ok(())
     }
 
-    /// Evaluates an assignment expression, modifying lvalue
-    /// and pushing rvalue onto stack.
-    fn assignment_expression(&mut self, node: &AssignmentExpression, env: &Environment<'_>) {
-        // currently only supporting `id (=|+=|-=) expr` (no subscript etc)
-        // TODO: lvalue evaluation for subscript, struct access, etc
+    /// Counts down the cell at `entry` upon reaching a label, setting
+    /// the cell at `matched` once that gets to zero.
+    fn reach_label(&mut self, entry: usize, matched: usize) {
+        let top = self.stack_pointer;
+
+        self.move_to(entry);
+        self.push('-');
+        self.if_zero(
+            entry,
+            top,
+            |cg| {
+                cg.move_to(matched);
+                cg.push_str("[-]+");
+            },
+            |_| {},
+        );
 
-        let (location, r#type) = env
-            .lookup_variable(&node.left.src)
-            .expect("Variable should be defined");
+        self.move_to(top);
+    }
 
-        let var_size = r#type.size();
+    /// Evaluates the condition of a `switch` and works out which label
+    /// it jumps to, as the position of the label among those directly
+    /// within its body (counting from 1), or 0 if there's none to jump
+    /// to. Pushes that as a single cell.
+    fn switch_entry(&mut self, node: &SwitchStatement, env: &Environment<'_>) -> CodegenResult {
+        let condition = &node.condition.child;
+        let r#type = ValueType::from_expression(condition, env)?;
+
+        if !r#type.is_integer() && r#type != ValueType::Bool {
+            return Err(Diagnostic::new(
+                format!("cannot switch on a value of type `{type}`"),
+                condition.span(),
+            ));
+        }
 
-        // space for stack value
-        self.move_head(var_size.cast_signed());
+        let size = r#type.size();
+        let (mut cases, mut default) = (vec![], None);
+
+        for (position, label) in (1..).zip(switch_labels(node)) {
+            if position > u8::MAX.into() {
+                return Err(Diagnostic::new(
+                    format!(
+                        "too many labels in one `switch` (at most {} are supported)",
+                        u8::MAX
+                    ),
+                    label.span,
+                ));
+            }
 
-        // evaluate and examine
-        match *node.operator {
-            AssignmentOperator::AssignEquals => {
-                self.expression(&node.right, env);
-                self.move_head(var_size.cast_signed() * -1);
+            let Some(ref value) = label.value else {
+                if default.replace(position).is_some() {
+                    return Err(Diagnostic::new(
+                        "multiple `default` labels in one `switch`",
+                        label.span,
+                    ));
+                }
+
+                continue;
+            };
+
+            let constant = constant_value(value, env)
+                .ok_or_else(|| Diagnostic::new("`case` value must be a constant", value.span()))?;
+            let bytes = constant.to_le_bytes()[..size].to_vec();
+
+            if cases.iter().any(|(_, other)| *other == bytes) {
+                return Err(Diagnostic::new(
+                    format!("duplicate `case` value {constant}"),
+                    value.span(),
+                ));
+            }
+
+            cases.push((position, bytes));
+        }
+
+        let entry = self.stack_pointer;
+        self.move_head(1);
+
+        let value = self.stack_pointer;
+        self.expression(condition, env)?;
+        let top = self.stack_pointer;
+
+        // at most one case matches
+        for (position, bytes) in cases {
+            self.copy_value(value, top, size, top + size);
+            for (cell, byte) in (top..).zip(bytes) {
+                self.move_to(cell);
+                self.push_n(byte.into(), '-');
             }
+            self.collapse(top, size);
+
+            self.if_zero(
+                top,
+                top + 1,
+                |cg| {
+                    cg.move_to(entry);
+                    cg.push_n(position, '+');
+                },
+                |_| {},
+            );
+
+            self.move_to(top);
+            self.push_str("[-]");
+        }
+
+        for cell in value..top {
+            self.move_to(cell);
+            self.push_str("[-]");
+        }
+
+        if let Some(default) = default {
+            self.if_zero(
+                entry,
+                value,
+                |cg| {
+                    cg.move_to(entry);
+                    cg.push_n(default, '+');
+                },
+                |_| {},
+            );
+        }
+
+        self.move_to(value);
+
+        Ok(())
+    }
+
+    /// Generates the body of a `switch` within `env` (the body's own
+    /// environment), with everything in it only running if the cell at
+    /// `gate` (if any) is nonzero. `label` generates whatever happens
+    /// upon reaching each label, given its position (see
+    /// `switch_entry`).
+    fn switch_body(
+        &mut self,
+        node: &SwitchStatement,
+        env: &mut Environment,
+        gate: Option<usize>,
+        mut label: impl FnMut(&mut Self, usize),
+    ) {
+        let mut position = 0;
+
+        for child in &node.body.children {
+            let children = match *child {
+                BlockChild::Statement(ref stmt) => match **stmt {
+                    Statement::CaseStatement(ref cs) => {
+                        position += 1;
+                        label(self, position);
+
+                        &cs.children[..]
+                    }
+                    _ => slice::from_ref(child),
+                },
+                _ => slice::from_ref(child),
+            };
+
+            for child in children {
+                self.block_child(child, env, gate);
+            }
+        }
+    }
+
+    /// Generates code for a `while` statement.
+    fn while_statement(&mut self, node: &WhileStatement, env: &Environment<'_>) -> CodegenResult {
+        // a `return` or `break` in the body stops the loop
+        let (loop_env, running) = self.loop_environment(&node.body, env);
+
+        // Examine condition
+        self.loop_condition(Some(&node.condition.child), running, &loop_env)?;
+        self.push('<');
+        self.stack_pointer -= 1;
+
+        // If cond != 0, clear and evaluate body
+        self.bf_loop(|cg| {
+            cg.push_str("[-]");
+
+            cg.loop_body(&node.body, &loop_env)?;
+
+            // Examine condition again so we can run it back
+            cg.loop_condition(Some(&node.condition.child), running, &loop_env)?;
+            cg.push('<');
+            cg.stack_pointer -= 1;
+
+            Ok(())
+        })?;
+
+        self.clear_environment(loop_env);
 
-            // with both += and -= we're only doing integers (which are currently just chars)
-            // so we can safely assume size = 1.
-            AssignmentOperator::PlusEquals => {
-                if r#type != ValueType::Char {
-                    unimplemented!("Non-integer types not doing plus-equals assignment");
+        Ok(())
+    }
+
+    /// Generates code for a `do` statement, which is like a `while`
+    /// statement except the body runs once before the condition is
+    /// first checked.
+    fn do_statement(&mut self, node: &DoStatement, env: &Environment<'_>) -> CodegenResult {
+        // a `return` or `break` in the body stops the loop
+        let (loop_env, running) = self.loop_environment(&node.body, env);
+
+        // Enter the loop the first time around regardless
+        self.push('+');
+
+        self.bf_loop(|cg| {
+            cg.push_str("[-]");
+
+            cg.loop_body(&node.body, &loop_env)?;
+
+            // Examine condition so we can run it back
+            cg.loop_condition(Some(&node.condition.child), running, &loop_env)?;
+            cg.push('<');
+            cg.stack_pointer -= 1;
+
+            Ok(())
+        })?;
+
+        self.clear_environment(loop_env);
+
+        Ok(())
+    }
+
+    /// Creates the environment for the body of a loop (within `env`),
+    /// pushing the cells its `break` and `continue` statements clear
+    /// if it has any (see `Loop::Gated`). Also returns the cell that
+    /// the loop has to check on top of its condition, if any.
+    fn loop_environment<'a>(
+        &mut self,
+        body: &Statement,
+        env: &'a Environment<'a>,
+    ) -> (Environment<'a>, Option<usize>) {
+        let mut loop_env = Environment::new(Some(env), self.stack_pointer);
+
+        if !may_jump(body) {
+            return (loop_env, loop_gate(body, env));
+        }
+
+        let (looping, iterating) = (self.stack_pointer, self.stack_pointer + 1);
+
+        self.push('+');
+        self.move_head(2);
+
+        loop_env.r#loop = Some(Loop::Gated { looping, iterating });
+
+        (loop_env, Some(looping))
+    }
+
+    /// Generates an iteration of a loop's `body`, within the loop's
+    /// environment (see `loop_environment`), setting the cell that
+    /// `continue` clears (if there is one) for the duration.
+    fn loop_body(&mut self, body: &Statement, env: &Environment<'_>) -> CodegenResult {
+        let Some(Loop::Gated { iterating, .. }) = env.r#loop else {
+            return self.statement(body, env);
+        };
+
+        let top = self.stack_pointer;
+
+        self.move_to(iterating);
+        self.push('+');
+        self.move_to(top);
+
+        self.statement(body, env)?;
+
+        self.move_to(iterating);
+        self.push_str("[-]");
+        self.move_to(top);
+
+        Ok(())
+    }
+
+    /// Generates code for a `break` statement (if `breaks`) or
+    /// `continue` statement, which gets out of the innermost loop (or
+    /// `switch`, for `break`) or its current iteration (see `Loop`).
+    fn jump_statement(&mut self, span: Span, breaks: bool, env: &Environment<'_>) -> CodegenResult {
+        let mut switches = vec![];
+        let target = env.loops().find(|r#loop| {
+            if !breaks && !r#loop.is_loop() {
+                switches.push(*r#loop);
+            }
+
+            breaks || r#loop.is_loop()
+        });
+
+        let Some(target) = target else {
+            return Err(Diagnostic::new(
+                if breaks {
+                    "`break` statement not within a loop or `switch`"
+                } else {
+                    "`continue` statement not within a loop"
+                },
+                span,
+            ));
+        };
+
+        let top = self.stack_pointer;
+
+        // `continue` gets out of any `switch` on the way
+        for cell in switches.into_iter().flat_map(Loop::flags) {
+            self.move_to(cell);
+            self.push_str("[-]");
+        }
+
+        match target {
+            Loop::Gated { looping, iterating } => {
+                if breaks {
+                    self.move_to(looping);
+                    self.push_str("[-]");
                 }
 
-                // push onto stack
-                self.identifier(&node.left, env);
-                self.expression(&node.right, env);
+                self.move_to(iterating);
+                self.push_str("[-]");
+                self.move_to(top);
+            }
+            Loop::Dispatched {
+                top: base,
+                exit,
+                next,
+            } => {
+                for cell in (base..top).rev() {
+                    self.move_to(cell);
+                    self.push_str("[-]");
+                }
 
-                // add & examine
-                self.move_head(-1);
-                self.push_str("[-<+>]");
-                self.move_head(-1);
+                self.jump(if breaks { exit } else { next });
+            }
+            Loop::Switch { matched } => {
+                self.move_to(matched);
+                self.push_str("[-]");
+                self.move_to(top);
             }
-            AssignmentOperator::MinusEquals => {
-                if r#type != ValueType::Char {
-                    unimplemented!("Non-integer types not doing minus-equals assignment");
+            Loop::DispatchedSwitch { top: base, exit } => {
+                for cell in (base..top).rev() {
+                    self.move_to(cell);
+                    self.push_str("[-]");
                 }
 
-                // push onto stack
-                self.identifier(&node.left, env);
-                self.expression(&node.right, env);
+                self.jump(exit);
+            }
+        }
 
-                // sub & examine
-                self.move_head(-1);
-                self.push_str("[-<->]");
-                self.move_head(-1);
+        Ok(())
+    }
+
+    /// Evaluates any expression and pushes its value onto stack.
+    fn expression(&mut self, expr: &Expression, env: &Environment<'_>) -> CodegenResult {
+        match *expr {
+            Expression::AssignmentExpression(ref ae) => self.assignment_expression(ae, env)?,
+            Expression::BinaryExpression(ref be) => self.binary_expression(be, env)?,
+            Expression::CallExpression(ref ce) => self.call_expression(ce, env)?,
+            Expression::CharLiteral(ref cl) => self.char_literal_expression(cl)?,
+            Expression::Identifier(ref id) => self.identifier(id, env)?,
+            Expression::NumberLiteral(ref nl) => {
+                let (num, r#type) = interpret_number_literal(nl)?;
+
+                self.push_constant(num, r#type);
+            }
+            Expression::True(_) => {
+                self.push('+');
+                self.move_head(1);
+            }
+            Expression::False(_) => self.move_head(1),
+            Expression::UpdateExpression(ref update_expr) => {
+                self.update_expression(update_expr, env)?
+            }
+            Expression::UnaryExpression(ref unary_expr) => {
+                self.unary_expression(unary_expr, env)?
+            }
+            Expression::ParenthesizedExpression(ref paren_expr) => {
+                self.parenthesized_expression(paren_expr, env)?
+            }
+            Expression::SubscriptExpression(ref subscript_expr) => {
+                self.subscript_expression(subscript_expr, env)?
+            }
+            Expression::FieldExpression(ref field_expr) => {
+                self.field_expression(field_expr, env)?
+            }
+            Expression::ConditionalExpression(ref cond_expr) => {
+                self.conditional_expression(cond_expr, env)?
+            }
+            Expression::CommaExpression(ref comma_expr) => {
+                self.discard(&comma_expr.left, env)?;
+                self.expression(&comma_expr.right, env)?
+            }
+            Expression::StringLiteral(ref sl) => {
+                return Err(Diagnostic::new(
+                    "string literals can only initialize `char` arrays or be printed",
+                    sl.span,
+                ));
             }
         }
 
-        let var_dist = self.stack_pointer - location;
+        Ok(())
+    }
 
-        // clear original var memory
-        self.push_n(var_dist, '<');
-        for _ in 0..var_size {
-            self.push_str("[-]>");
+    /// Pushes `value` onto stack as type `r#type`, one cell per byte,
+    /// least significant first (negative numbers come out in two's
+    /// complement).
+    fn push_constant(&mut self, value: i64, r#type: ValueType) {
+        for byte in value.to_le_bytes().into_iter().take(r#type.size()) {
+            self.push_n(byte.into(), '+');
+            self.move_head(1);
         }
-        self.push_n(var_dist - var_size, '>');
+    }
 
-        // copy into stack value and local variable
+    /// Evaluates an expression and pushes its value onto stack
+    /// as type `r#type`, converting between integer types if
+    /// need be.
+    fn expression_as(
+        &mut self,
+        expr: &Expression,
+        r#type: ValueType,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let expr_type = ValueType::from_expression(expr, env)?;
+
+        if expr_type != r#type && !(expr_type.is_integer() && r#type.is_integer()) {
+            return Err(Diagnostic::new(
+                format!("mismatched types: expected `{type}`, found `{expr_type}`"),
+                expr.span(),
+            ));
+        }
 
-        // for every cell in the temp value...
-        for _ in 0..var_size {
-            // while the temp cell is nonzero...
-            self.bf_loop(|cg| {
-                // subtract from temp, add to stack destination
-                cg.push_str("-<+");
-                // move to local variable cell
-                cg.push_n(var_dist - 1, '<');
-                // add to local
-                cg.push('+');
-                // move back to temp value's cell and repeat
-                cg.push_n(var_dist, '>');
-            });
-            // advance to next cell of temp
-            self.push('>');
+        // a constant can go straight in as the type it's wanted as
+        if r#type.is_integer()
+            && let Some(value) = constant_value(expr, env)
+        {
+            self.push_constant(value, r#type);
+
+            return Ok(());
         }
-        self.push('<');
 
-        // Now stack pointer is after first `right`, where it should be!
+        self.expression(expr, env)?;
+        self.convert(expr_type, r#type);
+
+        Ok(())
+    }
+
+    /// Evaluates an assignment expression, modifying lvalue
+    /// and pushing rvalue onto stack.
+    fn assignment_expression(
+        &mut self,
+        node: &AssignmentExpression,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let r#type = ValueType::from_expression(&node.left, env)?;
+        let var_size = r#type.size();
+
+        if let ValueType::Array(..) = r#type {
+            return Err(Diagnostic::new(
+                format!("cannot assign to an array of type `{type}`"),
+                node.left.span(),
+            ));
+        }
+
+        if !matches!(*node.operator, AssignmentOperator::AssignEquals(_)) && !r#type.is_integer() {
+            return Err(Diagnostic::new(
+                format!("cannot do arithmetic with a variable of type `{type}`"),
+                node.operator.span(),
+            ));
+        }
+
+        // where the value goes, which might first push an offset
+        let place = self.place(&node.left, env)?;
+
+        // space for stack value
+        let result = self.stack_pointer;
+        self.move_head(var_size.cast_signed());
+
+        // evaluate into temp, right after the space
+        let temp = self.stack_pointer;
+
+        match *node.operator {
+            AssignmentOperator::AssignEquals(_) => {
+                self.expression_as(&node.right, r#type.clone(), env)?;
+            }
+            // with the compound operators we're only doing integers,
+            // worked on in the type the binary operator would (see
+            // `operand_type`) and then converted back
+            ref operator => {
+                let operation = match *operator {
+                    AssignmentOperator::PlusEquals(_) => Arithmetic::Add,
+                    AssignmentOperator::MinusEquals(_) => Arithmetic::Subtract,
+                    AssignmentOperator::TimesEquals(_) => Arithmetic::Multiply,
+                    AssignmentOperator::DivideEquals(_) => Arithmetic::Divide,
+                    AssignmentOperator::ModuloEquals(_) => Arithmetic::Remainder,
+                    AssignmentOperator::AndEquals(_) => Arithmetic::And,
+                    AssignmentOperator::OrEquals(_) => Arithmetic::Or,
+                    AssignmentOperator::XorEquals(_) => Arithmetic::Xor,
+                    AssignmentOperator::ShiftLeftEquals(_) => Arithmetic::ShiftLeft,
+                    AssignmentOperator::ShiftRightEquals(_) => Arithmetic::ShiftRight,
+                    AssignmentOperator::AssignEquals(_) => unreachable!("handled above"),
+                };
+
+                let right_type = ValueType::from_expression(&node.right, env)?;
+                let operand_type = match operation {
+                    Arithmetic::ShiftLeft | Arithmetic::ShiftRight => r#type.promoted(),
+                    // a mismatch gets reported when converting
+                    _ => ValueType::common_type(&r#type, &right_type)
+                        .unwrap_or_else(|| r#type.clone()),
+                };
+
+                self.load(&place);
+                self.convert(r#type.clone(), operand_type.clone());
+                self.expression_as(&node.right, operand_type.clone(), env)?;
+
+                self.arithmetic(operation, operand_type.clone());
+                self.convert(operand_type, r#type);
+            }
+        }
+
+        // move into stack value and the lvalue
+        self.store(&place, temp, &[result]);
+
+        // Now the stack value is the last thing on stack, where it should be!
+        self.release(&place, result);
+
+        Ok(())
     }
 
     /// Evaluates and pushes onto stack a binary expression's
     /// value.
-    fn binary_expression(&mut self, node: &BinaryExpression, env: &Environment<'_>) {
+    fn binary_expression(
+        &mut self,
+        node: &BinaryExpression,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
         // this is a pretty big function, not sure how to shrink it
 
-        let push_left = |cg: &mut Self| cg.expression(&node.left, env);
-        let push_right = |cg: &mut Self| cg.expression(&node.right, env);
+        // also checks that the operands suit the operator
+        ValueType::from_binary_expression(node, env)?;
 
-        let left_type = ValueType::from_expression(&node.left, env);
-        let right_type = ValueType::from_expression(&node.right, env);
-
-        if left_type != right_type {
-            unimplemented!("Binary operators across types");
+        if let BinaryOperator::LogicalAnd(_) | BinaryOperator::LogicalOr(_) = *node.operator {
+            return self.logical_expression(node, env);
         }
 
+        let operand_type = ValueType::operand_type(node, env)?;
+        let size = operand_type.size();
+
+        let push_left = |cg: &mut Self| cg.expression_as(&node.left, operand_type.clone(), env);
+        let push_right = |cg: &mut Self| cg.expression_as(&node.right, operand_type.clone(), env);
+
         match *node.operator {
             // TODO: equality for all types
-            BinaryOperator::EqualsCheck => {
-                if !matches!(left_type, ValueType::Bool | ValueType::Char) {
-                    todo!("Equality checks for types outside of char and bool");
-                }
+            BinaryOperator::EqualsCheck(_) | BinaryOperator::NotEqualsCheck(_) => {
+                let equals = matches!(*node.operator, BinaryOperator::EqualsCheck(_));
 
-                // set flag to 1
-                self.push('+');
+                // set flag to 1 if checking for equality (0 otherwise)
+                let flag = self.stack_pointer;
+                if equals {
+                    self.push('+');
+                }
                 self.move_head(1);
 
-                push_left(self);
-                push_right(self);
+                let left = self.stack_pointer;
+                push_left(self)?;
+                let right = self.stack_pointer;
+                push_right(self)?;
 
-                // Subtract a - b
-                self.move_head(-1);
-                self.push_str("[-<->]");
-                // inspect difference (2nd value is cleared)
-                self.move_head(-1);
+                // Subtract a - b, cell by cell. No borrowing needed:
+                // they're equal only if every difference is zero.
+                for i in 0..size {
+                    self.move_to(right + i);
+                    self.bf_loop(|cg| {
+                        cg.push('-');
+                        cg.move_to(left + i);
+                        cg.push('-');
+                        cg.move_to(right + i);
+                    });
+                }
 
-                // if difference != 0 (they are NOT equal),
-                // clear diff and set flag to 0
-                self.bf_loop(|cg| {
-                    cg.push_str("[-]");
-                    cg.push_str("<->");
-                });
+                // if any difference != 0 (they are NOT equal),
+                // clear it and flip the flag
+                for i in 0..size {
+                    self.move_to(left + i);
+                    self.bf_loop(|cg| {
+                        cg.push_str("[-]");
+                        cg.move_to(flag);
+                        cg.push_str(if equals { "[-]" } else { "[-]+" });
+                        cg.move_to(left + i);
+                    });
+                }
+
+                self.move_to(left);
             }
-            BinaryOperator::NotEqualsCheck => {
-                // set flag = 0
+            BinaryOperator::LessThan(_)
+            | BinaryOperator::GreaterThan(_)
+            | BinaryOperator::LessThanOrEqual(_)
+            | BinaryOperator::GreaterThanOrEqual(_) => {
+                // Everything is phrased as `a < b` (possibly negated),
+                // where `a` and `b` may be the operands swapped.
+                let (swapped, negated) = match *node.operator {
+                    BinaryOperator::LessThan(_) => (false, false),
+                    BinaryOperator::GreaterThan(_) => (true, false),
+                    BinaryOperator::LessThanOrEqual(_) => (true, true),
+                    _ => (false, true),
+                };
+
+                // Each operand gets an extra (zero) cell on top, which
+                // the subtraction `a - b` borrows from if `a < b`.
+                let result = self.stack_pointer;
+                let left = result;
+                let right = left + size + 1;
+
+                push_left(self)?;
                 self.move_head(1);
+                push_right(self)?;
+                self.move_head(1);
+                let top = self.stack_pointer;
+
+                // Flipping the sign bits of two's complement numbers
+                // makes them compare the same way as unsigned ones.
+                if operand_type.is_signed() {
+                    for high_byte in [left + size - 1, right + size - 1] {
+                        self.move_to(high_byte);
+                        self.push_n(128, '+');
+                    }
+                }
 
-                push_left(self);
-                push_right(self);
-
-                // Subtract a - b
-                {
-                    self.push_str("<[<->-]");
+                let (a, b) = if swapped {
+                    (right, left)
+                } else {
+                    (left, right)
+                };
+                self.subtract_from(a, b, size + 1, top);
 
-                    self.stack_pointer -= 1;
+                // only the borrow cell matters now
+                for cell in a..a + size {
+                    self.move_to(cell);
+                    self.push_str("[-]");
                 }
 
-                // inspect result
-                self.move_head(-1);
+                if negated {
+                    self.move_to(result);
+                    self.push('+');
+                }
 
-                // if difference != 0 (they ARE unequal)
-                // clear difference and set flag to one
+                // it's 255 if there was a borrow, zero otherwise
+                let borrow = a + size;
+                self.move_to(borrow);
                 self.bf_loop(|cg| {
                     cg.push_str("[-]");
-                    cg.push_str("<+>");
+                    cg.move_to(result);
+                    cg.push(if negated { '-' } else { '+' });
+                    cg.move_to(borrow);
                 });
+
+                self.move_to(result + 1);
             }
-            BinaryOperator::Plus => {
-                push_left(self);
-                push_right(self);
-                self.move_head(-1);
-                self.push_str("[<+>-]");
-            }
-            BinaryOperator::Minus => {
-                push_left(self);
-                push_right(self);
-                self.move_head(-1);
-                self.push_str("[<->-]");
+            ref operator => {
+                let operation = match *operator {
+                    BinaryOperator::Plus(_) => Arithmetic::Add,
+                    BinaryOperator::Minus(_) => Arithmetic::Subtract,
+                    BinaryOperator::Times(_) => Arithmetic::Multiply,
+                    BinaryOperator::Divide(_) => Arithmetic::Divide,
+                    BinaryOperator::Modulo(_) => Arithmetic::Remainder,
+                    BinaryOperator::BitwiseAnd(_) => Arithmetic::And,
+                    BinaryOperator::BitwiseOr(_) => Arithmetic::Or,
+                    BinaryOperator::BitwiseXor(_) => Arithmetic::Xor,
+                    BinaryOperator::ShiftLeft(_) => Arithmetic::ShiftLeft,
+                    BinaryOperator::ShiftRight(_) => Arithmetic::ShiftRight,
+                    _ => unreachable!("handled above"),
+                };
+
+                push_left(self)?;
+                push_right(self)?;
+
+                self.arithmetic(operation, operand_type);
             }
         }
+
+        Ok(())
+    }
+
+    /// Evaluates a function call, pushing its return value onto
+    /// stack. User-defined functions are inlined, their parameters
+    /// being locals initialized with the arguments.
+    fn call_expression(&mut self, node: &CallExpression, env: &Environment<'_>) -> CodegenResult {
+        let arguments = &node.arguments.children;
+
+        if let Some(builtin) = Builtin::from_name(&node.function.src) {
+            return self.builtin_call(builtin, node, env);
+        }
+
+        let function = env.function(&node.function)?;
+
+        check_arguments(node, function.parameters.len())?;
+
+        if function.dispatched {
+            return self.dispatched_call(function, arguments, env);
+        }
+
+        let result = self.stack_pointer;
+        self.move_head(function.return_type.size().cast_signed());
+
+        // The body was already checked where it was defined,
+        // so any problems within it have been reported.
+        let reported = self.diagnostics.len();
+
+        // the callee sees the globals wherever its caller does
+        let mut scope = Environment::new(Some(env.root()), self.stack_pointer);
+        scope.globals_base = Some(env.globals_base());
+
+        self.function_body(function, result, &scope, |cg| {
+            for (argument, (_, r#type)) in arguments.iter().zip(&function.parameters) {
+                cg.expression_as(argument, r#type.clone(), env)?;
+            }
+
+            Ok(())
+        })?;
+
+        self.diagnostics.truncate(reported);
+
+        Ok(())
     }
 
     /// Evaluates and pushes onto stack a character's
     /// corresponding value.
-    fn char_literal_expression(&mut self, node: &CharLiteral) {
-        assert!(
-            node.children.len() == 1,
-            "expected one character in char literal"
-        );
-
-        let child = node.children.first().unwrap();
-
-        let c = match *child {
-            CharLiteralChildren::Character(ref c) => c.src.chars().next().unwrap(),
-            CharLiteralChildren::EscapeSequence(ref es) => match es.src.as_str() {
-                r"\'" => '\'',
-                r#"\""# => '\"',
-                r"\?" => '?',
-                r"\\" => '\\',
-                r"\a" => '\x07',
-                r"\b" => '\x08',
-                r"\f" => '\x0c',
-                r"\n" => '\n',
-                r"\r" => '\r',
-                r"\t" => '\t',
-                r"\v" => '\x0b',
-                esc if esc.starts_with(r"\x") => unimplemented!(),
-                esc if esc.starts_with(r"\u") => unimplemented!(),
-                esc if esc.starts_with(r"\U") => unimplemented!(),
-                esc if esc.starts_with('\\') => unimplemented!(),
-                _ => unreachable!(),
-            },
-        };
+    fn char_literal_expression(&mut self, node: &CharLiteral) -> CodegenResult {
+        let c = char_literal_value(node)?;
 
-        self.push_n(c as usize, '+');
+        self.push_n(c.into(), '+');
         self.move_head(1);
+
+        Ok(())
     }
 
     /// Looks up variable in `env` and pushes its value to stack.
-    fn identifier(&mut self, node: &Identifier, env: &Environment<'_>) {
-        let (var_location, var_type) = env
-            .lookup_variable(node.src.as_str())
-            .expect("variable should've been found");
-        let var_distance = self.stack_pointer - var_location;
+    fn identifier(&mut self, node: &Identifier, env: &Environment<'_>) -> CodegenResult {
+        if let Some(value) = env.lookup_constant(&node.src) {
+            self.push_constant(value, ValueType::Int);
+
+            return Ok(());
+        }
+
+        let (var_location, var_type) = env.variable(node)?;
         let var_size = var_type.size();
 
-        // Copy to two locations: stack and temp (adjacent)
+        // only ever indexed into
+        if let ValueType::Array(..) = var_type {
+            return Err(Diagnostic::new(
+                format!("cannot use array `{}` as a value", node.src),
+                node.span,
+            ));
+        }
 
-        // move to var location
-        self.push_n(var_distance, '<');
-        // for each of the local's cells...
-        for _ in 0..var_size {
-            // until cell empty...
-            self.bf_loop(|cg| {
-                // subtract from local cell
-                cg.push('-');
-                // move back to stack
-                cg.push_n(var_distance, '>');
-                // incremement stack and temp cell
-                cg.push_str("+>+");
-                // move back to local
-                cg.push_n(var_distance + 1, '<');
-            });
-            // now move to next cell
-            self.push('>');
+        // Copy to stack, using the space just past it as a temp
+        let value_location = self.stack_pointer;
+        self.copy_value(
+            var_location,
+            value_location,
+            var_size,
+            value_location + var_size,
+        );
+
+        // move back to top of stack
+        self.move_to(value_location + var_size);
+
+        Ok(())
+    }
+
+    /// Evaluates a subscript expression (i.e., `a[i]`) and pushes
+    /// the element's value onto stack.
+    fn subscript_expression(
+        &mut self,
+        node: &SubscriptExpression,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let place = self.subscript_place(node, env)?;
+
+        let value = self.stack_pointer;
+        self.load(&place);
+        self.release(&place, value);
+
+        Ok(())
+    }
+
+    /// Generates code for an update expression, which pushes the
+    /// updated value of its lvalue if the operator is prefixed and the
+    /// old one if it's postfixed.
+    fn update_expression(
+        &mut self,
+        update_expr: &UpdateExpression,
+        env: &Environment,
+    ) -> CodegenResult {
+        let r#type = ValueType::from_expression(&update_expr.argument, env)?;
+
+        // this function is majorly uninvolved from the type system, sadly
+        if !r#type.is_integer() {
+            return Err(Diagnostic::new(
+                format!("cannot increment or decrement a value of type `{type}`"),
+                update_expr.span,
+            ));
         }
-        // move back to temp
-        self.push_n(var_distance, '>');
 
-        // Move temp back into source
+        // the AST doesn't tell the two apart, but the source does
+        let prefix =
+            update_expr.operator.span().start_byte < update_expr.argument.span().start_byte;
+        let size = r#type.size();
+
+        let update = |cg: &mut Self, location: usize| {
+            let top = cg.stack_pointer;
 
-        // for each temp cell...
-        for _ in 0..var_size {
-            // while temp cell isn't empty...
+            match *update_expr.operator {
+                UpdateOperator::PlusPlus(_) => cg.increment(location, size, top),
+                UpdateOperator::MinusMinus(_) => cg.decrement(location, size, top),
+            }
+
+            cg.move_to(top);
+        };
+
+        let place = self.place(&update_expr.argument, env)?;
+        let result = self.stack_pointer;
+
+        if let Some(location) = place.location() {
+            // a variable (or part of one) can just be updated in place,
+            // either after or before pushing its value
+            if prefix {
+                update(self, location);
+            }
+
+            self.load(&place);
+
+            if !prefix {
+                update(self, location);
+            }
+        } else {
+            // push the old value, then store an updated copy of it (or
+            // update it first and store a copy of the new value)
+            self.load(&place);
+
+            if prefix {
+                update(self, result);
+            }
+
+            let value = result + size;
+            self.copy_value(result, value, size, value + size);
+            self.move_to(value + size);
+
+            if !prefix {
+                update(self, value);
+            }
+
+            self.store(&place, value, &[]);
+        }
+
+        self.release(&place, result);
+
+        Ok(())
+    }
+
+    /// Evaluates and pushes onto stack a unary expression's value.
+    fn unary_expression(&mut self, node: &UnaryExpression, env: &Environment<'_>) -> CodegenResult {
+        let r#type = ValueType::from_unary_expression(node, env)?;
+        let size = r#type.size();
+
+        match *node.operator {
+            UnaryOperator::Plus(_) => self.expression_as(&node.argument, r#type, env),
+            // 0 - x
+            UnaryOperator::Minus(_) => {
+                let result = self.stack_pointer;
+                self.move_head(size.cast_signed());
+
+                self.expression_as(&node.argument, r#type, env)?;
+                self.subtract_from(result, result + size, size, self.stack_pointer);
+                self.move_to(result + size);
+
+                Ok(())
+            }
+            UnaryOperator::BitwiseNot(_) => {
+                let result = self.stack_pointer;
+
+                self.expression_as(&node.argument, r#type, env)?;
+                self.complement(result, size, self.stack_pointer);
+
+                Ok(())
+            }
+            UnaryOperator::Not(_) => {
+                // set result to 1
+                let result = self.stack_pointer;
+                self.push('+');
+                self.move_head(1);
+
+                // Examine argument
+                self.condition(&node.argument, env)?;
+                self.move_head(-1);
+
+                // If it's true, clear it and set result to 0
+                self.bf_loop(|cg| {
+                    cg.push_str("[-]");
+                    cg.move_to(result);
+                    cg.push('-');
+                    cg.move_to(result + 1);
+                });
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Evaluates a `&&` or `||` expression and pushes its (`bool`)
+    /// value onto stack. The right operand is only evaluated if the
+    /// left one doesn't already decide the result, as in C.
+    fn logical_expression(
+        &mut self,
+        node: &BinaryExpression,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        if expression_splits(&node.right, env) {
+            return self.dispatched_logical_expression(node, env);
+        }
+
+        let result = self.stack_pointer;
+
+        if let BinaryOperator::LogicalAnd(_) = *node.operator {
+            // result starts at 0
+            self.move_head(1);
+
+            // Examine left
+            let left = self.stack_pointer;
+            self.condition(&node.left, env)?;
+            self.move_head(-1);
+
+            // If left is true, clear it and let right decide
+            self.bf_loop(|cg| -> CodegenResult {
+                cg.push_str("[-]");
+
+                // right goes where left was
+                cg.condition(&node.right, env)?;
+                cg.move_head(-1);
+
+                cg.bf_loop(|cg| {
+                    cg.push_str("[-]");
+                    cg.move_to(result);
+                    cg.push('+');
+                    cg.move_to(left);
+                });
+
+                Ok(())
+            })?;
+        } else {
+            // result starts at 0, "otherwise" flag at 1
+            let otherwise = result + 1;
+            self.move_head(1);
+            self.push('+');
+            self.move_head(1);
+
+            // Examine left
+            let left = self.stack_pointer;
+            self.condition(&node.left, env)?;
+            self.move_head(-1);
+
+            // If left is true, clear it and the flag, and set result to 1
             self.bf_loop(|cg| {
-                // subtract from temp cell
+                cg.push_str("[-]");
+                cg.move_to(otherwise);
                 cg.push('-');
-                // move to variable
-                cg.push_n(var_distance + 1, '<');
-                // increment variable cell
+                cg.move_to(result);
                 cg.push('+');
-                // move back to temp
-                cg.push_n(var_distance + 1, '>');
+                cg.move_to(left);
             });
-            // advance to next cell
-            self.push('>');
+
+            // If the flag is still set, clear it and let right decide
+            self.move_to(otherwise);
+            self.bf_loop(|cg| -> CodegenResult {
+                cg.push('-');
+
+                // right goes where the flag was
+                cg.condition(&node.right, env)?;
+                cg.move_head(-1);
+
+                cg.bf_loop(|cg| {
+                    cg.push_str("[-]");
+                    cg.move_to(result);
+                    cg.push('+');
+                    cg.move_to(otherwise);
+                });
+
+                Ok(())
+            })?;
         }
-        // move back to top of stack
-        self.push_n(var_size, '<');
 
-        // now we've moved by one var_size
-        self.stack_pointer += var_size;
+        self.move_to(result + 1);
+
+        Ok(())
     }
 
-    /// Generates code for an update expression
-    ///
-    /// For technical reasons, this function cannot easily ascertain whether the update operator was prefixed or postfixed so it currently assumes it's postfixed.
-    fn update_expression(&mut self, update_expr: &UpdateExpression, env: &Environment) {
-        // TODO: this thing currently just assumes the update was postfixed.
-        // there's no way to structurally check in the AST whether
-        // it was prefixed or postfixed, which SUCKS so i'd have
-        // to probably check sourcecode
-
-        let dist = match *update_expr.argument {
-            Expression::Identifier(ref id) => {
-                let (var_location, r#type) = env
-                    .lookup_variable(&id.src)
-                    .expect("Variable should have been defined");
-
-                // this function is majorly uninvolved from the type system, sadly
-                if r#type != ValueType::Char {
-                    unimplemented!("Non-integer types not supported for update expressions");
-                }
+    /// Evaluates a conditional (`?:`) expression and pushes its value
+    /// onto stack. Only the branch that the condition picks is
+    /// evaluated, much like an `if` statement with an `else`.
+    fn conditional_expression(
+        &mut self,
+        node: &ConditionalExpression,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        if expression_splits(&node.consequence, env) || expression_splits(&node.alternative, env) {
+            return self.dispatched_conditional_expression(node, env);
+        }
 
-                self.stack_pointer - var_location
-            }
-            _ => unimplemented!("Non-identifiers not implemented for update expressions"),
-        };
+        let r#type = ValueType::from_conditional_expression(node, env)?;
+        let size = r#type.size();
 
-        // make space for final stack value
+        // result starts at 0, flag at 1
+        let result = self.stack_pointer;
+        let flag = result + size;
+        self.move_to(flag);
+        self.push('+');
         self.move_head(1);
 
-        // move value from variable to temp and inspect
-        self.push_n(dist + 1, '<');
-        self.bf_loop(|cg| {
+        // Examine condition
+        let cond = self.stack_pointer;
+        self.condition(&node.condition, env)?;
+        self.move_head(-1);
+
+        // If cond != 0 (true), clear the flag and evaluate consequence
+        self.bf_loop(|cg| -> CodegenResult {
+            cg.push_str("[-]<->");
+
+            // consequence goes where cond was
+            cg.move_to(cond);
+            cg.expression_as(&node.consequence, r#type.clone(), env)?;
+            cg.move_value(cond, &[result], size);
+            cg.move_to(cond);
+
+            Ok(())
+        })?;
+
+        // If flag != 0 (i.e., cond false), evaluate alternative
+        self.move_to(flag);
+        self.bf_loop(|cg| -> CodegenResult {
             cg.push('-');
-            cg.push_n(dist + 1, '>');
-            cg.push('+');
-            cg.push_n(dist + 1, '<');
-        });
-        self.push_n(dist + 1, '>');
 
-        // update temp according to operator
-        self.push(match *update_expr.operator {
-            UpdateOperator::PlusPlus => '+',
-            UpdateOperator::MinusMinus => '-',
-        });
+            // alternative goes where the flag was
+            cg.expression_as(&node.alternative, r#type.clone(), env)?;
+            cg.move_value(flag, &[result], size);
+            cg.move_to(flag);
 
-        // copy into variable and to stack
-        self.bf_loop(|cg| {
-            cg.push_str("-<+");
-            cg.push_n(dist, '<');
-            cg.push('+');
-            cg.push_n(dist + 1, '>');
-        });
+            Ok(())
+        })?;
 
-        // we are now after the stack value, so we're done!
+        Ok(())
     }
 
     /// Evaluates a parenthesized expression (most cases,
     /// this is just syntactically required or to indicate
     /// operation order in expressions) and pushes its
     /// value onto stack.
-    fn parenthesized_expression(&mut self, node: &ParenthesizedExpression, env: &Environment<'_>) {
-        self.expression(&node.child, env);
-    }
-
-    /// Pushes the value of each of the passed arguments
-    /// onto stack sequentially.
-    fn argument_list(&mut self, node: &ArgumentList, env: &Environment<'_>) {
-        for argument in &node.children {
-            self.expression(argument, env);
-        }
+    fn parenthesized_expression(
+        &mut self,
+        node: &ParenthesizedExpression,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        self.expression(&node.child, env)
     }
 }
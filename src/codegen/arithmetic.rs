@@ -0,0 +1,633 @@
+//! Building blocks for working with integers that span several
+//! cells.
+//!
+//! Integers are stored little-endian, one byte per cell. Every
+//! function here addresses cells absolutely and takes `top`, the
+//! lowest cell known to be zero with nothing but zeroes above it,
+//! which it may use (and must give back zeroed) as scratch space.
+//!
+//! Cells only ever count up and down, so bitwise operations split
+//! each byte into bits by halving it over and over.
+
+use super::{Codegen, ValueType};
+
+/// An operation on two integers of the same type, producing
+/// another of that type.
+#[derive(Clone, Copy)]
+pub(super) enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    And,
+    Or,
+    Xor,
+    /// Shifts by the right operand's lowest byte, i.e., its value
+    /// modulo 256 (C leaves shifting by the width or more undefined
+    /// anyway).
+    ShiftLeft,
+    /// Shifts like `ShiftLeft`, filling in with the sign bit for
+    /// signed types.
+    ShiftRight,
+}
+
+impl Codegen {
+    /// Generates code that runs `zero` if the cell at `cell` is zero,
+    /// and `nonzero` otherwise, leaving the head at `cell`.
+    ///
+    /// Takes constant time and doesn't disturb `cell`, using `top` as
+    /// a flag along with the cell as far above `top` as `top` is above
+    /// `cell`. Whatever the branches generate may only use cells above
+    /// `top` for scratch.
+    pub(super) fn if_zero(
+        &mut self,
+        cell: usize,
+        top: usize,
+        zero: impl FnOnce(&mut Self),
+        nonzero: impl FnOnce(&mut Self),
+    ) {
+        debug_assert!(cell < top);
+
+        let distance = top - cell;
+
+        self.move_to(top);
+        self.push('+');
+        self.move_to(cell);
+
+        // Entered if the cell is nonzero, leaving at `top`
+        // (now zero) instead of `cell`.
+        self.bf_loop(|cg| {
+            nonzero(cg);
+
+            cg.move_to(top);
+            cg.push('-');
+            cg.stack_pointer = cell;
+        });
+
+        // Lands on `top` (still one) if the cell was zero, and on the
+        // cell `distance` above it (always zero) if it wasn't.
+        self.move_head(distance.cast_signed());
+
+        // Entered if the cell was zero, leaving at the same place
+        // the other branch lands.
+        self.bf_loop(|cg| {
+            cg.push('-');
+
+            cg.move_to(cell);
+            zero(cg);
+
+            cg.move_to(top + distance);
+        });
+
+        self.stack_pointer = top + distance;
+        self.move_to(cell);
+    }
+
+    /// Adds one to the `size`-cell integer at `location`, carrying
+    /// into the higher cells (and wrapping around on overflow).
+    pub(super) fn increment(&mut self, location: usize, size: usize, top: usize) {
+        self.move_to(location);
+        self.push('+');
+
+        if size > 1 {
+            self.if_zero(
+                location,
+                top,
+                |cg| cg.increment(location + 1, size - 1, top + 1),
+                |_| {},
+            );
+        }
+    }
+
+    /// Subtracts one from the `size`-cell integer at `location`,
+    /// borrowing from the higher cells (and wrapping around on
+    /// underflow).
+    pub(super) fn decrement(&mut self, location: usize, size: usize, top: usize) {
+        if size > 1 {
+            self.if_zero(
+                location,
+                top,
+                |cg| cg.decrement(location + 1, size - 1, top + 1),
+                |_| {},
+            );
+        }
+
+        self.move_to(location);
+        self.push('-');
+    }
+
+    /// Applies `operation` to the two integers of type `r#type` on top
+    /// of the stack (the left operand first), leaving the result in
+    /// place of the left operand with the head just past it.
+    ///
+    /// Everything wraps around on overflow. Dividing by zero gives
+    /// -1 (every bit set) and leaves the dividend as the remainder.
+    pub(super) fn arithmetic(&mut self, operation: Arithmetic, r#type: ValueType) {
+        let size = r#type.size();
+        let left = self.stack_pointer - 2 * size;
+        let right = left + size;
+
+        match operation {
+            Arithmetic::Add => self.add_into(left, right, size, right + size),
+            Arithmetic::Subtract => self.subtract_from(left, right, size, right + size),
+            Arithmetic::Multiply => self.multiply(left, size),
+            Arithmetic::Divide | Arithmetic::Remainder => {
+                // quotient first, then remainder
+                self.divide(left, size, r#type.is_signed());
+
+                let unwanted = match operation {
+                    Arithmetic::Divide => right,
+                    _ => left,
+                };
+
+                for cell in unwanted..unwanted + size {
+                    self.move_to(cell);
+                    self.push_str("[-]");
+                }
+
+                if let Arithmetic::Remainder = operation {
+                    self.move_value(right, &[left], size);
+                }
+            }
+            Arithmetic::And | Arithmetic::Or | Arithmetic::Xor => {
+                self.bitwise(operation, left, size);
+            }
+            Arithmetic::ShiftLeft | Arithmetic::ShiftRight => {
+                self.shift(operation, left, size, r#type.is_signed());
+            }
+        }
+
+        self.move_to(right);
+    }
+
+    /// Multiplies the `size`-cell integer at `left` by the one right
+    /// after it, leaving the product at `left` and clearing the rest.
+    fn multiply(&mut self, left: usize, size: usize) {
+        let right = left + size;
+        let product = right + size;
+        let top = product + size;
+
+        // Long multiplication, a byte of `right` at a time: add `left`
+        // (shifted along to that byte) once for every unit in it.
+        for i in 0..size {
+            self.move_to(right + i);
+            self.bf_loop(|cg| {
+                cg.push('-');
+                cg.add_copy_into(product + i, left, size - i, top);
+                cg.move_to(right + i);
+            });
+        }
+
+        for cell in left..right {
+            self.move_to(cell);
+            self.push_str("[-]");
+        }
+
+        self.move_value(product, &[left], size);
+    }
+
+    /// Divides the `size`-cell integer at `left` by the one right
+    /// after it, truncating towards zero. Leaves the quotient at
+    /// `left` and the remainder (which has the dividend's sign)
+    /// right after it, with everything above cleared.
+    pub(super) fn divide(&mut self, left: usize, size: usize, signed: bool) {
+        let dividend = left;
+        let divisor = dividend + size;
+        // the divisor's extra (zero) cell sits in between
+        let quotient = divisor + size + 1;
+        let remainder = quotient + size;
+        let dividend_sign = remainder + size + 1;
+        let divisor_sign = dividend_sign + 1;
+        let top = divisor_sign + 1;
+
+        // Divide magnitudes, fixing up the signs at the end.
+        if signed {
+            for (operand, sign) in [(dividend, dividend_sign), (divisor, divisor_sign)] {
+                self.sign_bit(operand + size - 1, sign, top);
+                self.if_zero(sign, top, |_| {}, |cg| cg.negate(operand, size, top + 1));
+            }
+        }
+
+        // Work out whether the divisor is nonzero...
+        self.copy_value(divisor, top, size, top + size);
+        self.collapse(top, size);
+
+        // ...and if so, do long division in base 256, working out a
+        // byte of the quotient at a time from the most significant.
+        let nonzero = top;
+        let otherwise = top + 1;
+        let top = top + 2;
+
+        self.move_to(otherwise);
+        self.push('+');
+        self.move_to(nonzero);
+        self.bf_loop(|cg| {
+            cg.push('-');
+
+            for i in (0..size).rev() {
+                // Bring down the next byte (i.e., shift the
+                // remainder a cell up and put the byte at the bottom).
+                for cell in (remainder..remainder + size).rev() {
+                    cg.move_value(cell, &[cell + 1], 1);
+                }
+                cg.move_value(dividend + i, &[remainder], 1);
+
+                // Subtract the divisor for as long as that doesn't
+                // make the remainder negative, counting the times
+                // into the quotient's byte (which can't exceed 255).
+                let again = top;
+                cg.move_to(again);
+                cg.push('+');
+                cg.bf_loop(|cg| {
+                    cg.push('-');
+
+                    cg.subtract_copy_from(remainder, divisor, size + 1, top + 1);
+                    cg.if_zero(
+                        remainder + size,
+                        top + 1,
+                        |cg| {
+                            cg.move_to(quotient + i);
+                            cg.push('+');
+                            cg.move_to(again);
+                            cg.push('+');
+                        },
+                        // it went negative, so put it back
+                        |cg| cg.add_copy_into(remainder, divisor, size + 1, top + 2),
+                    );
+
+                    cg.move_to(again);
+                });
+            }
+
+            cg.move_to(otherwise);
+            cg.push('-');
+            cg.move_to(nonzero);
+        });
+
+        // Dividing by zero gives -1 with the dividend as the remainder.
+        self.move_to(otherwise);
+        self.bf_loop(|cg| {
+            cg.push('-');
+
+            for cell in quotient..quotient + size {
+                cg.move_to(cell);
+                cg.push('-');
+            }
+            cg.move_value(dividend, &[remainder], size);
+
+            // Keeps the quotient from being negated below.
+            if signed {
+                cg.copy_value(dividend_sign, divisor_sign, 1, top);
+            }
+
+            cg.move_to(otherwise);
+        });
+
+        if signed {
+            // The quotient is negative if exactly one operand was
+            // (and the remainder if the dividend was).
+            self.move_to(dividend_sign);
+            self.bf_loop(|cg| {
+                cg.push('-');
+                cg.negate(remainder, size, top);
+                cg.if_zero(divisor_sign, top, |cg| cg.push('+'), |cg| cg.push('-'));
+                cg.move_to(dividend_sign);
+            });
+
+            self.move_to(divisor_sign);
+            self.bf_loop(|cg| {
+                cg.push('-');
+                cg.negate(quotient, size, top);
+                cg.move_to(divisor_sign);
+            });
+        }
+
+        for cell in divisor..divisor + size {
+            self.move_to(cell);
+            self.push_str("[-]");
+        }
+
+        self.move_value(quotient, &[left], size);
+        self.move_value(remainder, &[left + size], size);
+    }
+
+    /// Applies the bitwise `operation` to the `size`-cell integer at
+    /// `left` and the one right after it, a bit at a time from the
+    /// lowest, leaving the result at `left` and clearing the rest.
+    fn bitwise(&mut self, operation: Arithmetic, left: usize, size: usize) {
+        let right = left + size;
+        let result = right + size;
+        let (sum, right_bit, bit) = (result + 1, result + 2, result + 3);
+        let top = result + 4;
+
+        for i in 0..size {
+            for place in 0..8 {
+                self.halve(left + i, sum, top);
+                self.halve(right + i, right_bit, top);
+                self.move_value(right_bit, &[sum], 1);
+
+                // the two bits add up to 0, 1 or 2
+                self.move_to(sum);
+                match operation {
+                    Arithmetic::And => self.bf_loop(|cg| {
+                        cg.push('-');
+                        cg.bf_loop(|cg| {
+                            cg.push('-');
+                            cg.move_to(bit);
+                            cg.push('+');
+                            cg.move_to(sum);
+                        });
+                    }),
+                    Arithmetic::Or => self.bf_loop(|cg| {
+                        cg.push_str("[-]");
+                        cg.move_to(bit);
+                        cg.push('+');
+                        cg.move_to(sum);
+                    }),
+                    Arithmetic::Xor => self.bf_loop(|cg| {
+                        cg.push('-');
+                        cg.move_to(bit);
+                        cg.push('+');
+                        cg.move_to(sum);
+                        cg.bf_loop(|cg| {
+                            cg.push('-');
+                            cg.move_to(bit);
+                            cg.push('-');
+                            cg.move_to(sum);
+                        });
+                    }),
+                    _ => unreachable!("not a bitwise operation"),
+                }
+
+                self.move_to(bit);
+                self.bf_loop(|cg| {
+                    cg.push('-');
+                    cg.move_to(result);
+                    cg.push_n(1 << place, '+');
+                    cg.move_to(bit);
+                });
+            }
+
+            self.move_value(result, &[left + i], 1);
+        }
+    }
+
+    /// Shifts the `size`-cell integer at `left` by the lowest byte of
+    /// the one right after it (see `Arithmetic::ShiftLeft`), a bit at a
+    /// time, leaving the result at `left` and clearing the rest.
+    fn shift(&mut self, operation: Arithmetic, left: usize, size: usize, signed: bool) {
+        let count = left + size;
+        let top = count + size;
+
+        for cell in count + 1..top {
+            self.move_to(cell);
+            self.push_str("[-]");
+        }
+
+        self.move_to(count);
+        self.bf_loop(|cg| {
+            cg.push('-');
+
+            match operation {
+                // doubling it, that is
+                Arithmetic::ShiftLeft => {
+                    cg.copy_value(left, top, size, top + size);
+                    cg.add_into(left, top, size, top + size);
+                }
+                Arithmetic::ShiftRight => cg.shift_right_once(left, size, signed, top),
+                _ => unreachable!("not a shift"),
+            }
+
+            cg.move_to(count);
+        });
+    }
+
+    /// Shifts the `size`-cell integer at `location` right by one bit,
+    /// keeping its sign bit as it was if `signed`.
+    fn shift_right_once(&mut self, location: usize, size: usize, signed: bool, top: usize) {
+        let high_byte = location + size - 1;
+        let sign = top;
+        // the bit each byte loses, which the one below gains
+        let bits = sign + 1;
+        let top = bits + size;
+
+        if signed {
+            self.sign_bit(high_byte, sign, top);
+        }
+
+        for i in 0..size {
+            self.halve(location + i, bits + i, top);
+        }
+
+        self.move_to(bits);
+        self.push_str("[-]");
+
+        for (from, to) in (bits + 1..)
+            .zip(location..high_byte)
+            .chain(signed.then_some((sign, high_byte)))
+        {
+            self.move_to(from);
+            self.bf_loop(|cg| {
+                cg.push('-');
+                cg.move_to(to);
+                cg.push_n(128, '+');
+                cg.move_to(from);
+            });
+        }
+    }
+
+    /// Halves the cell at `cell` (rounding down), setting the cell at
+    /// `bit` (which must be zero) to the bit that gets shifted out.
+    fn halve(&mut self, cell: usize, bit: usize, top: usize) {
+        let half = top;
+
+        // every other unit goes towards the half
+        self.move_to(cell);
+        self.bf_loop(|cg| {
+            cg.push('-');
+            cg.if_zero(
+                bit,
+                top + 1,
+                |cg| {
+                    cg.move_to(bit);
+                    cg.push('+');
+                },
+                |cg| {
+                    cg.move_to(bit);
+                    cg.push('-');
+                    cg.move_to(half);
+                    cg.push('+');
+                },
+            );
+            cg.move_to(cell);
+        });
+
+        self.move_value(half, &[cell], 1);
+    }
+
+    /// Flips every bit of the `size`-cell integer at `location`.
+    pub(super) fn complement(&mut self, location: usize, size: usize, top: usize) {
+        for cell in location..location + size {
+            // 255 minus the cell
+            self.move_value(cell, &[top], 1);
+            self.move_to(cell);
+            self.push('-');
+
+            self.move_to(top);
+            self.bf_loop(|cg| {
+                cg.push('-');
+                cg.move_to(cell);
+                cg.push('-');
+                cg.move_to(top);
+            });
+        }
+    }
+
+    /// Replaces the `size`-cell integer at `location` with its
+    /// negation (i.e., its two's complement).
+    pub(super) fn negate(&mut self, location: usize, size: usize, top: usize) {
+        self.move_value(location, &[top], size);
+        self.subtract_from(location, top, size, top + size);
+    }
+
+    /// Adds the `size`-cell integer at `source` to the one at
+    /// `destination`, clearing `source`.
+    pub(super) fn add_into(&mut self, destination: usize, source: usize, size: usize, top: usize) {
+        for i in 0..size {
+            self.move_to(source + i);
+            self.bf_loop(|cg| {
+                cg.push('-');
+                cg.increment(destination + i, size - i, top);
+                cg.move_to(source + i);
+            });
+        }
+    }
+
+    /// Subtracts the `size`-cell integer at `source` from the one at
+    /// `destination`, clearing `source`.
+    pub(super) fn subtract_from(
+        &mut self,
+        destination: usize,
+        source: usize,
+        size: usize,
+        top: usize,
+    ) {
+        for i in 0..size {
+            self.move_to(source + i);
+            self.bf_loop(|cg| {
+                cg.push('-');
+                cg.decrement(destination + i, size - i, top);
+                cg.move_to(source + i);
+            });
+        }
+    }
+
+    /// Adds the `size`-cell integer at `source` to the one at
+    /// `destination`, leaving `source` as it was.
+    pub(super) fn add_copy_into(
+        &mut self,
+        destination: usize,
+        source: usize,
+        size: usize,
+        top: usize,
+    ) {
+        let temp = top;
+
+        for i in 0..size {
+            self.move_to(source + i);
+            self.bf_loop(|cg| {
+                cg.push('-');
+                cg.move_to(temp);
+                cg.push('+');
+                cg.increment(destination + i, size - i, top + 1);
+                cg.move_to(source + i);
+            });
+
+            self.move_value(temp, &[source + i], 1);
+        }
+    }
+
+    /// Subtracts the `size`-cell integer at `source` from the one at
+    /// `destination`, leaving `source` as it was.
+    pub(super) fn subtract_copy_from(
+        &mut self,
+        destination: usize,
+        source: usize,
+        size: usize,
+        top: usize,
+    ) {
+        let temp = top;
+
+        for i in 0..size {
+            self.move_to(source + i);
+            self.bf_loop(|cg| {
+                cg.push('-');
+                cg.move_to(temp);
+                cg.push('+');
+                cg.decrement(destination + i, size - i, top + 1);
+                cg.move_to(source + i);
+            });
+
+            self.move_value(temp, &[source + i], 1);
+        }
+    }
+
+    /// Adds one to the cell at `flag` (which must be below `top`) if
+    /// the cell at `cell` has its high bit set, i.e., if it's the
+    /// most significant byte of a negative signed integer.
+    pub(super) fn sign_bit(&mut self, cell: usize, flag: usize, top: usize) {
+        let counter = top;
+
+        // Adding 128 wraps the cell past zero exactly
+        // when its high bit is set, so watch for that.
+        self.move_to(counter);
+        self.push_n(128, '+');
+        self.bf_loop(|cg| {
+            cg.push('-');
+
+            cg.move_to(cell);
+            cg.push('+');
+            cg.if_zero(
+                cell,
+                top + 1,
+                |cg| {
+                    cg.move_to(flag);
+                    cg.push('+');
+                },
+                |_| {},
+            );
+
+            cg.move_to(counter);
+        });
+
+        // then put it back
+        self.move_to(cell);
+        self.push_n(128, '-');
+    }
+
+    /// Replaces the `size`-cell value at `location` with a single
+    /// cell holding one if any of its cells were nonzero (or zero
+    /// otherwise), clearing the rest.
+    pub(super) fn collapse(&mut self, location: usize, size: usize) {
+        let flag = location + size;
+
+        for i in 0..size {
+            self.move_to(location + i);
+            self.bf_loop(|cg| {
+                cg.push_str("[-]");
+                cg.move_to(flag);
+                cg.push_str("[-]+");
+                cg.move_to(location + i);
+            });
+        }
+
+        self.move_to(flag);
+        self.bf_loop(|cg| {
+            cg.push('-');
+            cg.move_to(location);
+            cg.push('+');
+            cg.move_to(flag);
+        });
+    }
+}
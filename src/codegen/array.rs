@@ -0,0 +1,617 @@
+//! Arrays, and getting at their elements with indices that are only
+//! known at runtime.
+//!
+//! BF can only move the head by distances fixed in the code, so `a[i]`
+//! can't be reached directly. Instead, every variable holding an array
+//! gets a rail of work cells in front of it, which starts out zeroed:
+//!
+//! ```text
+//! hole, flag, counter (low byte), 2 scratch cells, counter (high byte,
+//! if any), backup of the counter, value
+//! ```
+//!
+//! To get at a cell `n` cells into the variable, everything after the
+//! hole (the packet) travels `n` cells along. Each step shifts the cell
+//! in front of the packet into the hole behind it and the packet up by
+//! one, so the packet ends up right in front of the cell it was sent to,
+//! with the cells it passed lined up behind it. Walking back puts every
+//! one of them back in place.
+//!
+//! The code at the far end is generated as though the packet had never
+//! moved, since it's all relative anyway. Values travel to and from it
+//! in the packet's value cells.
+
+use crate::{diagnostic::Diagnostic, treesitter_wrapper::*};
+
+use super::{
+    Codegen, CodegenResult, Environment, ValueType, arithmetic::Arithmetic, constant_value,
+};
+
+/// Location of the hole within a rail, which every step fills with the
+/// cell the packet passes.
+const HOLE: usize = 0;
+
+/// Location of the cell within a rail that keeps the packet going
+/// while the counter isn't zero.
+const FLAG: usize = 1;
+
+/// Location of the counter's low byte within a rail.
+const COUNTER: usize = 2;
+
+/// Location of the two scratch cells within a rail, right after the
+/// counter's low byte (which `Codegen::if_zero` relies on).
+const SCRATCH: usize = 3;
+
+/// The work cells in front of a variable holding arrays.
+#[derive(Clone, Copy)]
+pub(super) struct Rail {
+    /// Size of the counter, which has to reach every cell of the
+    /// variable.
+    counter: usize,
+    /// Size of the largest value that ever has to be carried along.
+    value: usize,
+}
+
+/// Where an lvalue (something that can be assigned to) lives.
+pub(super) struct Place {
+    /// Location of the variable it's (part of).
+    variable: usize,
+    /// The rail in front of that variable, if it has one.
+    rail: Option<Rail>,
+    /// The type of the lvalue itself.
+    pub(super) r#type: ValueType,
+    /// How many cells into the variable it lies.
+    offset: Offset,
+}
+
+/// How many cells into its variable a `Place` lies.
+#[derive(Clone, Copy)]
+enum Offset {
+    /// Known at compile time.
+    Fixed(usize),
+    /// Only known at runtime, and held (as the rail's counter type)
+    /// on the stack at this location.
+    Indexed(usize),
+}
+
+impl Rail {
+    /// Returns the rail that a variable of type `r#type` needs, which
+    /// it only does if it holds any arrays.
+    pub(super) fn of(r#type: &ValueType) -> Option<Self> {
+        /// Returns the size of the largest value within an array in a
+        /// value of type `r#type`, if it holds any arrays.
+        fn largest_element(r#type: &ValueType) -> Option<usize> {
+            match *r#type {
+                ValueType::Array(ref element, _) => Some(largest_value(element)),
+                ValueType::Struct(ref layout) => layout
+                    .fields
+                    .iter()
+                    .filter_map(|(_, field)| largest_element(field))
+                    .max(),
+                _ => None,
+            }
+        }
+
+        /// Returns the size of the largest value (i.e., anything but an
+        /// array) that a value of type `r#type` is or holds.
+        fn largest_value(r#type: &ValueType) -> usize {
+            match *r#type {
+                ValueType::Array(ref element, _) => largest_value(element),
+                ref other => other.size(),
+            }
+        }
+
+        Some(Self {
+            counter: if r#type.size() <= 1 << 8 { 1 } else { 2 },
+            value: largest_element(r#type)?,
+        })
+    }
+
+    /// Returns the locations of the counter's bytes within the rail,
+    /// least significant first.
+    fn counter_cells(&self) -> impl Iterator<Item = usize> {
+        [COUNTER, SCRATCH + 2].into_iter().take(self.counter)
+    }
+
+    /// Returns the location of the counter's backup within the rail,
+    /// which counts the way back.
+    fn backup(&self) -> usize {
+        SCRATCH + 2 + self.counter - 1
+    }
+
+    /// Returns the location of the value cells within the rail.
+    fn value(&self) -> usize {
+        self.backup() + self.counter
+    }
+
+    /// Returns the number of cells in the rail.
+    pub(super) fn size(&self) -> usize {
+        self.value() + self.value
+    }
+
+    /// Returns the type that offsets into the variable are computed
+    /// as, which is the counter's.
+    fn counter_type(&self) -> ValueType {
+        match self.counter {
+            1 => ValueType::UnsignedChar,
+            _ => ValueType::UnsignedShort,
+        }
+    }
+}
+
+impl Place {
+    /// Returns the location of the place itself, if it's known at
+    /// compile time.
+    pub(super) fn location(&self) -> Option<usize> {
+        match self.offset {
+            Offset::Fixed(offset) => Some(self.variable + offset),
+            Offset::Indexed(_) => None,
+        }
+    }
+}
+
+/// Returns whether `expr` refers to somewhere that a `Place` can be
+/// worked out for, rather than a value that's only ever on the stack.
+pub(super) fn is_place(expr: &Expression) -> bool {
+    match *expr {
+        Expression::Identifier(_) => true,
+        Expression::ParenthesizedExpression(ref pe) => is_place(&pe.child),
+        Expression::SubscriptExpression(ref se) => is_place(&se.argument),
+        Expression::FieldExpression(ref fe) => is_place(&fe.argument),
+        _ => false,
+    }
+}
+
+/// Returns the type of the array that `declarator` (occurring within
+/// `env`) declares, whose elements are of type `element`.
+///
+/// Only the outermost array (i.e., the innermost declarator) may leave
+/// out its size, which then comes from its `initializer`.
+pub(super) fn array_type(
+    declarator: &ArrayDeclarator,
+    element: ValueType,
+    initializer: Option<&Initializer>,
+    env: &Environment,
+) -> CodegenResult<ValueType> {
+    let length = match (declarator.size.as_deref(), initializer) {
+        (Some(size), _) => match constant_value(size, env) {
+            Some(length) if length > 0 => length.unsigned_abs() as usize,
+            Some(_) => {
+                return Err(Diagnostic::new(
+                    "size of array must be greater than zero",
+                    size.span(),
+                ));
+            }
+            None => {
+                return Err(Diagnostic::new(
+                    "size of array must be a constant",
+                    size.span(),
+                ));
+            }
+        },
+        (None, Some(Initializer::InitializerList(list)))
+            if matches!(*declarator.declarator, Declarator::Identifier(_))
+                && !list.children.is_empty() =>
+        {
+            list.children.len()
+        }
+        (None, Some(Initializer::Expression(value)))
+            if matches!(*declarator.declarator, Declarator::Identifier(_))
+                && matches!(**value, Expression::StringLiteral(_)) =>
+        {
+            match ValueType::from_expression(value, env)? {
+                ValueType::Array(_, length) => length,
+                _ => unreachable!("string literals are arrays"),
+            }
+        }
+        (None, _) => return Err(Diagnostic::new("missing size of array", declarator.span)),
+    };
+
+    let r#type = ValueType::Array(Box::new(element), length);
+
+    // there'd be no counting that far along the rail
+    if r#type.size() > 1 << 16 {
+        return Err(Diagnostic::new(
+            format!("array of type `{type}` is too large"),
+            declarator.span,
+        ));
+    }
+
+    Ok(r#type)
+}
+
+impl ValueType {
+    /// Returns the type of the element that a subscript expression
+    /// occurring within `env` refers to.
+    pub(super) fn from_subscript_expression(
+        node: &SubscriptExpression,
+        env: &Environment,
+    ) -> CodegenResult<Self> {
+        let array_type = Self::from_expression(&node.argument, env)?;
+
+        let Self::Array(ref element, length) = array_type else {
+            return Err(Diagnostic::new(
+                format!("cannot index into a value of type `{array_type}`"),
+                node.argument.span(),
+            ));
+        };
+
+        let index_type = Self::from_expression(&node.index, env)?;
+
+        if !index_type.is_integer() {
+            return Err(Diagnostic::new(
+                format!("array index must be an integer, found `{index_type}`"),
+                node.index.span(),
+            ));
+        }
+
+        if let Some(index) = constant_value(&node.index, env)
+            && !(0..length as i64).contains(&index)
+        {
+            return Err(Diagnostic::new(
+                format!("index {index} is out of bounds for `{array_type}`"),
+                node.index.span(),
+            ));
+        }
+
+        Ok((**element).clone())
+    }
+}
+
+impl Codegen {
+    /// Works out where the lvalue `expr` lives. If that depends on an
+    /// index only known at runtime, the offset into its variable gets
+    /// pushed onto stack (which `release` clears again).
+    pub(super) fn place(
+        &mut self,
+        expr: &Expression,
+        env: &Environment<'_>,
+    ) -> CodegenResult<Place> {
+        match *expr {
+            Expression::Identifier(ref id) => {
+                let (variable, r#type) = env.variable(id)?;
+
+                Ok(Place {
+                    variable,
+                    rail: Rail::of(&r#type),
+                    r#type,
+                    offset: Offset::Fixed(0),
+                })
+            }
+            Expression::ParenthesizedExpression(ref pe) => self.place(&pe.child, env),
+            Expression::SubscriptExpression(ref se) => self.subscript_place(se, env),
+            Expression::FieldExpression(ref fe) => self.field_place(fe, env),
+            _ => Err(Diagnostic::new("expression is not assignable", expr.span())),
+        }
+    }
+
+    /// Works out where the element that a subscript expression refers
+    /// to lives (see `place`).
+    pub(super) fn subscript_place(
+        &mut self,
+        node: &SubscriptExpression,
+        env: &Environment<'_>,
+    ) -> CodegenResult<Place> {
+        let element = ValueType::from_subscript_expression(node, env)?;
+        let mut place = self.place(&node.argument, env)?;
+        let size = element.size();
+
+        place.offset = match (constant_value(&node.index, env), place.offset) {
+            (Some(index), Offset::Fixed(offset)) => {
+                Offset::Fixed(offset + index.unsigned_abs() as usize * size)
+            }
+            (index, offset) => {
+                let rail = place.rail.expect("variables holding arrays have a rail");
+                let counter_type = rail.counter_type();
+
+                // the index (scaled to cells) goes on top of any offset
+                // already pushed, then the two are added up
+                let location = self.stack_pointer;
+
+                match index {
+                    Some(index) => self.push_constant(index * size as i64, counter_type.clone()),
+                    None => {
+                        self.expression_as(&node.index, counter_type.clone(), env)?;
+
+                        if size > 1 {
+                            self.push_constant(size as i64, counter_type.clone());
+                            self.arithmetic(Arithmetic::Multiply, counter_type.clone());
+                        }
+                    }
+                }
+
+                match offset {
+                    Offset::Fixed(0) => Offset::Indexed(location),
+                    Offset::Fixed(offset) => {
+                        self.push_constant(offset as i64, counter_type.clone());
+                        self.arithmetic(Arithmetic::Add, counter_type);
+
+                        Offset::Indexed(location)
+                    }
+                    Offset::Indexed(offset) => {
+                        self.arithmetic(Arithmetic::Add, counter_type);
+
+                        Offset::Indexed(offset)
+                    }
+                }
+            }
+        };
+        place.r#type = element;
+
+        Ok(place)
+    }
+
+    /// Narrows `place` down to the part of it `cells` cells further
+    /// in, which is of type `r#type` (e.g., a struct's field).
+    pub(super) fn narrow(&mut self, place: &mut Place, cells: usize, r#type: ValueType) {
+        match place.offset {
+            Offset::Fixed(offset) => place.offset = Offset::Fixed(offset + cells),
+            Offset::Indexed(_) if cells == 0 => {}
+            Offset::Indexed(_) => {
+                let counter_type = self.rail(place).counter_type();
+
+                self.push_constant(cells as i64, counter_type.clone());
+                self.arithmetic(Arithmetic::Add, counter_type);
+            }
+        }
+
+        place.r#type = r#type;
+    }
+
+    /// Pushes the value at `place` onto stack.
+    pub(super) fn load(&mut self, place: &Place) {
+        let size = place.r#type.size();
+        let top = self.stack_pointer;
+
+        match place.offset {
+            Offset::Fixed(offset) => {
+                self.copy_value(place.variable + offset, top, size, top + size);
+            }
+            Offset::Indexed(offset) => {
+                self.walk(place, offset, top, |cg, target, value, hole| {
+                    for i in 0..size {
+                        cg.copy_value(target + i, value + i, 1, hole);
+                    }
+                });
+
+                let rail = self.rail(place);
+                let header = place.variable - rail.size();
+
+                self.move_value(header + rail.value(), &[top], size);
+            }
+        }
+
+        self.move_to(top + size);
+    }
+
+    /// Moves the value on top of the stack (at `value`) into `place`,
+    /// as well as onto each of `copies`, clearing it.
+    pub(super) fn store(&mut self, place: &Place, value: usize, copies: &[usize]) {
+        let size = place.r#type.size();
+        let top = value + size;
+
+        match place.offset {
+            Offset::Fixed(offset) => {
+                let destination = place.variable + offset;
+
+                for cell in destination..destination + size {
+                    self.move_to(cell);
+                    self.push_str("[-]");
+                }
+
+                self.move_value(value, &[&[destination], copies].concat(), size);
+            }
+            Offset::Indexed(offset) => {
+                let rail = self.rail(place);
+                let header = place.variable - rail.size();
+
+                self.move_value(value, &[&[header + rail.value()], copies].concat(), size);
+                self.walk(place, offset, top, |cg, target, value, _| {
+                    for cell in target..target + size {
+                        cg.move_to(cell);
+                        cg.push_str("[-]");
+                    }
+
+                    cg.move_value(value, &[target], size);
+                });
+            }
+        }
+
+        self.move_to(value);
+    }
+
+    /// Clears the offset that `place` pushed (if any) from under the
+    /// value at `value` (of the place's type), which takes its spot.
+    pub(super) fn release(&mut self, place: &Place, value: usize) {
+        let size = place.r#type.size();
+
+        if let Offset::Indexed(offset) = place.offset {
+            for cell in offset..value {
+                self.move_to(cell);
+                self.push_str("[-]");
+            }
+
+            self.move_value(value, &[offset], size);
+            self.move_to(offset + size);
+        } else {
+            self.move_to(value + size);
+        }
+    }
+
+    /// Returns the rail in front of the variable `place` is in, which
+    /// it must have if it's indexed.
+    fn rail(&self, place: &Place) -> Rail {
+        place.rail.expect("variables holding arrays have a rail")
+    }
+
+    /// Sends the packet along the rail of `place`'s variable as many
+    /// cells as the offset at `offset` says, runs `access` there, then
+    /// brings the packet back. The offset is left as it was, and `top`
+    /// is used as scratch.
+    ///
+    /// `access` is given where the place's cells, the packet's value
+    /// cells and the (zeroed) hole would be if the packet hadn't moved.
+    fn walk(
+        &mut self,
+        place: &Place,
+        offset: usize,
+        top: usize,
+        access: impl FnOnce(&mut Self, usize, usize, usize),
+    ) {
+        let rail = self.rail(place);
+        let header = place.variable - rail.size();
+
+        // the counter and its backup both start off as the offset
+        for (i, cell) in rail.counter_cells().enumerate() {
+            self.move_value(
+                offset + i,
+                &[header + cell, header + rail.backup() + i, top + i],
+                1,
+            );
+        }
+        self.move_value(top, &[offset], rail.counter);
+
+        self.step_along(header, rail, true);
+
+        access(self, place.variable, header + rail.value(), header + HOLE);
+
+        for (i, cell) in rail.counter_cells().enumerate() {
+            self.move_value(header + rail.backup() + i, &[header + cell], 1);
+        }
+
+        self.step_along(header, rail, false);
+    }
+
+    /// Moves the packet of the rail at `header` one cell along (or
+    /// back, if not `forward`) until its counter runs out.
+    fn step_along(&mut self, header: usize, rail: Rail, forward: bool) {
+        // On the way back, the hole moves below the rail's start, so
+        // the steps are generated as though the rail began a cell
+        // later (which is fine, since they're all relative).
+        let base = if forward { header } else { header + 1 };
+        let next = if forward { base + 1 } else { base - 1 };
+
+        self.rail_flag(header, rail);
+        self.move_to(header + FLAG);
+        self.stack_pointer = base + FLAG;
+
+        self.bf_loop(|cg| {
+            cg.push('-');
+
+            let low = base + COUNTER;
+            if rail.counter > 1 {
+                cg.if_zero(
+                    low,
+                    base + SCRATCH,
+                    |cg| {
+                        cg.move_to(base + SCRATCH + 2);
+                        cg.push('-');
+                    },
+                    |_| {},
+                );
+            }
+            cg.move_to(low);
+            cg.push('-');
+
+            if forward {
+                cg.move_value(base + rail.size(), &[base + HOLE], 1);
+
+                for cell in (HOLE + 1..rail.size()).rev() {
+                    cg.move_value(base + cell, &[base + cell + 1], 1);
+                }
+            } else {
+                for cell in HOLE + 1..rail.size() {
+                    cg.move_value(base + cell, &[base + cell - 1], 1);
+                }
+
+                cg.move_value(base - 1, &[base + rail.size() - 1], 1);
+            }
+
+            cg.rail_flag(next, rail);
+            cg.move_to(next + FLAG);
+            cg.stack_pointer = base + FLAG;
+        });
+
+        self.stack_pointer = header + FLAG;
+    }
+
+    /// Sets the flag of the rail at `header` if its counter isn't zero.
+    fn rail_flag(&mut self, header: usize, rail: Rail) {
+        let (flag, scratch) = (header + FLAG, header + SCRATCH);
+
+        for cell in rail.counter_cells() {
+            self.copy_value(header + cell, scratch, 1, scratch + 1);
+
+            self.move_to(scratch);
+            self.bf_loop(|cg| {
+                cg.push_str("[-]");
+                cg.move_to(flag);
+                cg.push_str("[-]+");
+                cg.move_to(scratch);
+            });
+        }
+    }
+
+    /// Generates an initializer list for a value of type `r#type` at
+    /// `location`, which must be zeroed. Whatever it leaves out stays
+    /// zero.
+    pub(super) fn initializer_list(
+        &mut self,
+        list: &InitializerList,
+        r#type: &ValueType,
+        location: usize,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        // where each element (or field) lies within the value
+        let members: Vec<(usize, &ValueType)> = match *r#type {
+            ValueType::Array(ref element, length) => (0..length)
+                .map(|i| (i * element.size(), &**element))
+                .collect(),
+            ValueType::Struct(ref layout) => layout.members().collect(),
+            _ => {
+                return Err(Diagnostic::new(
+                    format!("cannot initialize a value of type `{type}` with an initializer list"),
+                    list.span,
+                ));
+            }
+        };
+
+        if let Some(extra) = list.children.get(members.len()) {
+            return Err(Diagnostic::new(
+                format!("too many initializers for `{type}`"),
+                extra.span(),
+            ));
+        }
+
+        for (initializer, &(offset, member)) in list.children.iter().zip(&members) {
+            let location = location + offset;
+
+            match *initializer {
+                Initializer::InitializerList(ref inner) => {
+                    self.initializer_list(inner, member, location, env)?;
+                }
+                Initializer::Expression(ref value) => {
+                    if let ValueType::Array(..) = *member {
+                        if let Expression::StringLiteral(ref sl) = **value {
+                            self.string_initializer(sl, member, location)?;
+                            continue;
+                        }
+
+                        return Err(Diagnostic::new(
+                            format!("missing braces around initializer for `{member}`"),
+                            value.span(),
+                        ));
+                    }
+
+                    let top = self.stack_pointer;
+                    self.expression_as(value, member.clone(), env)?;
+
+                    self.move_value(top, &[location], member.size());
+                    self.move_to(top);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+//! Functions that cranium provides itself, whose code is generated
+//! inline wherever they're called.
+
+use crate::{diagnostic::Diagnostic, treesitter_wrapper::*};
+
+use super::{Codegen, CodegenResult, Environment, ValueType, check_arguments, string_value};
+
+/// A function that cranium provides itself.
+#[derive(Clone, Copy)]
+pub(super) enum Builtin {
+    Putchar,
+    Getchar,
+    Puts,
+    Print,
+    Printf,
+}
+
+impl Builtin {
+    /// Every builtin, along with the name it's called by.
+    const ALL: [(&str, Self); 5] = [
+        ("putchar", Self::Putchar),
+        ("getchar", Self::Getchar),
+        ("puts", Self::Puts),
+        ("print", Self::Print),
+        ("printf", Self::Printf),
+    ];
+
+    /// Returns the builtin called `name`, if there is one.
+    pub(super) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|&&(other, _)| other == name)
+            .map(|&(_, builtin)| builtin)
+    }
+
+    /// Returns the type of value that a call to the builtin gives.
+    pub(super) fn return_type(self) -> ValueType {
+        match self {
+            Self::Getchar => ValueType::Int,
+            Self::Putchar | Self::Puts | Self::Print | Self::Printf => ValueType::Void,
+        }
+    }
+}
+
+impl Codegen {
+    /// Evaluates a call to a builtin, pushing its return value (if
+    /// any) onto stack.
+    pub(super) fn builtin_call(
+        &mut self,
+        builtin: Builtin,
+        node: &CallExpression,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let arguments = &node.arguments.children;
+
+        match builtin {
+            Builtin::Putchar => {
+                check_arguments(node, 1)?;
+
+                // only the low byte gets printed, as in C
+                self.expression_as(&arguments[0], ValueType::Char, env)?;
+
+                self.move_head(-1);
+                self.push_str(".[-]");
+            }
+            Builtin::Getchar => {
+                check_arguments(node, 0)?;
+
+                self.getchar();
+            }
+            // `puts` adds a newline, as in C, while `print` doesn't
+            Builtin::Puts | Builtin::Print => {
+                check_arguments(node, 1)?;
+
+                let Expression::StringLiteral(ref sl) = arguments[0] else {
+                    return Err(Diagnostic::new(
+                        format!("`{}` takes a string literal", node.function.src),
+                        arguments[0].span(),
+                    ));
+                };
+
+                let mut text = string_value(sl)?;
+                if let Builtin::Puts = builtin {
+                    text.push(b'\n');
+                }
+
+                self.print_bytes(&text);
+            }
+            Builtin::Printf => self.printf(node, env)?,
+        }
+
+        Ok(())
+    }
+
+    /// Reads a byte of input and pushes it as an `int`.
+    ///
+    /// The cell is set to 255 before reading, so that past the end of
+    /// input it holds 255 unless the BF implementation sets it to 0.
+    /// Either way, 255 reads as -1 (i.e., `EOF`) rather than 255, so
+    /// an input byte of 255 can't be told apart from the end of input
+    /// (the cell is all BF has to go on).
+    fn getchar(&mut self) {
+        let low = self.stack_pointer;
+        let (high, temp) = (low + 1, low + 2);
+
+        self.push_str("-,");
+
+        // the high byte is 255 unless the low one + 1 isn't zero
+        self.move_to(high);
+        self.push('-');
+        self.copy_value(low, temp, 1, temp + 1);
+        self.move_to(temp);
+        self.push('+');
+        self.bf_loop(|cg| {
+            cg.push_str("[-]");
+            cg.move_to(high);
+            cg.push('+');
+            cg.move_to(temp);
+        });
+    }
+}
@@ -0,0 +1,828 @@
+//! Generating functions that can't be inlined (recursive ones, and
+//! whatever calls them) as numbered blocks of one dispatch loop.
+//!
+//! Every call to such a function gets a frame on the tape, starting
+//! right at the caller's stack top. A frame begins with its control
+//! cell, which holds the number of the next block to run, followed by
+//! two scratch cells for the dispatch loop, the return value, the
+//! number of the caller's block to return to, a copy of the globals
+//! region (see `globals`), and finally the parameters and locals.
+//!
+//! Each iteration of the loop runs whichever block the control cell
+//! under the head asks for, and every block ends with the head on a
+//! control cell: its own frame's to jump within the function, the
+//! callee's to make a call, or its own (now holding the caller's block
+//! number) to return. The caller's block then takes over from there,
+//! which works out since it knows where it made the call.
+//!
+//! Dispatched functions are generated as though their frame began at
+//! 0, since the code never needs to know where it really is.
+
+use std::{collections::HashMap, mem};
+
+use crate::{diagnostic::Diagnostic, treesitter_wrapper::*};
+
+use super::{
+    Codegen, CodegenResult, Environment, Frame, Function, Loop, Return, ValueType, may_goto,
+    may_jump, may_return, switch_labels,
+};
+
+/// Location of the control cell within a frame.
+const CONTROL: usize = 0;
+
+/// Location of the return value within a frame, after the control
+/// cell and the dispatch loop's scratch cells.
+const RESULT: usize = 3;
+
+/// The blocks generated so far for the dispatch loop.
+#[derive(Default)]
+pub(super) struct Dispatch {
+    /// Code of each block, numbered from 1 since 0 stops the loop.
+    blocks: Vec<String>,
+    /// The block being generated (into `Codegen::output`), if it's
+    /// one that can ever run.
+    current: Option<usize>,
+    /// Number of the first block of each function called so far.
+    entries: HashMap<String, usize>,
+    /// Functions that have been called but not generated yet.
+    pending: Vec<String>,
+}
+
+/// Returns whether `env` is within a dispatched function (as
+/// opposed to an inlined one), so that its code is split into blocks.
+pub(super) fn dispatched(env: &Environment) -> bool {
+    env.frame()
+        .is_some_and(|frame| matches!(frame.exit, Return::Dispatched { .. }))
+}
+
+/// Returns whether `call` is a call to a dispatched function.
+fn is_dispatched_call(call: &CallExpression, env: &Environment) -> bool {
+    env.root()
+        .functions
+        .get(&call.function.src)
+        .is_some_and(|function| function.dispatched)
+}
+
+/// Returns whether `stmt` has to be split into several blocks, which
+/// is the case within a dispatched function if it has a `return`, a
+/// `goto`, a `break` or `continue` of a loop that was split itself, or
+/// a call to a dispatched function (all of which end a block).
+pub(super) fn splits(stmt: &Statement, env: &Environment) -> bool {
+    let jumps = || {
+        may_jump(stmt)
+            && matches!(
+                env.innermost_loop(),
+                Some(Loop::Dispatched { .. } | Loop::DispatchedSwitch { .. })
+            )
+    };
+
+    dispatched(env)
+        && (may_return(stmt)
+            || may_goto(stmt)
+            || jumps()
+            || statement_calls(stmt, &mut |call| is_dispatched_call(call, env)))
+}
+
+/// Returns whether evaluating `expr` has to be split into several
+/// blocks (see `splits`).
+pub(super) fn expression_splits(expr: &Expression, env: &Environment) -> bool {
+    dispatched(env) && expression_calls(expr, &mut |call| is_dispatched_call(call, env))
+}
+
+/// Returns whether any call within `block` satisfies `predicate`.
+pub(super) fn block_calls(
+    block: &CompoundStatement,
+    predicate: &mut impl FnMut(&CallExpression) -> bool,
+) -> bool {
+    block
+        .children
+        .iter()
+        .any(|child| block_child_calls(child, predicate))
+}
+
+/// Returns whether any call within `child` satisfies `predicate`.
+fn block_child_calls(
+    child: &BlockChild,
+    predicate: &mut impl FnMut(&CallExpression) -> bool,
+) -> bool {
+    match *child {
+        BlockChild::Declaration(ref decl) => declaration_calls(decl, predicate),
+        BlockChild::Statement(ref stmt) => statement_calls(stmt, predicate),
+        BlockChild::TypeDefinition(_)
+        | BlockChild::StructSpecifier(_)
+        | BlockChild::EnumSpecifier(_) => false,
+    }
+}
+
+/// Returns whether any call within `decl` satisfies `predicate`.
+fn declaration_calls(
+    decl: &Declaration,
+    predicate: &mut impl FnMut(&CallExpression) -> bool,
+) -> bool {
+    match *decl.declarator {
+        Declarator::InitDeclarator(ref init) => initializer_calls(&init.value, predicate),
+        Declarator::Identifier(_)
+        | Declarator::TypeIdentifier(_)
+        | Declarator::FunctionDeclarator(_)
+        | Declarator::ArrayDeclarator(_)
+        | Declarator::FieldIdentifier(_) => false,
+    }
+}
+
+/// Returns whether any call within `initializer` satisfies `predicate`.
+fn initializer_calls(
+    initializer: &Initializer,
+    predicate: &mut impl FnMut(&CallExpression) -> bool,
+) -> bool {
+    match *initializer {
+        Initializer::InitializerList(ref list) => list
+            .children
+            .iter()
+            .any(|initializer| initializer_calls(initializer, predicate)),
+        Initializer::Expression(ref expr) => expression_calls(expr, predicate),
+    }
+}
+
+/// Returns whether any call within `stmt` satisfies `predicate`.
+fn statement_calls(stmt: &Statement, predicate: &mut impl FnMut(&CallExpression) -> bool) -> bool {
+    match *stmt {
+        Statement::CaseStatement(ref cs) => cs
+            .children
+            .iter()
+            .any(|child| block_child_calls(child, predicate)),
+        Statement::CompoundStatement(ref cs) => block_calls(cs, predicate),
+        Statement::DoStatement(ref ds) => {
+            statement_calls(&ds.body, predicate) || expression_calls(&ds.condition.child, predicate)
+        }
+        Statement::ExpressionStatement(ref es) => es
+            .children
+            .iter()
+            .any(|expr| expression_calls(expr, predicate)),
+        Statement::ForStatement(ref fs) => {
+            let initializer = fs.initializer.iter().any(|init| match *init {
+                ForLoopInitializer::Declaration(ref decl) => declaration_calls(decl, predicate),
+                ForLoopInitializer::Expression(ref expr) => expression_calls(expr, predicate),
+            });
+
+            initializer
+                || [&fs.condition, &fs.update]
+                    .into_iter()
+                    .flatten()
+                    .any(|expr| expression_calls(expr, predicate))
+                || statement_calls(&fs.body, predicate)
+        }
+        Statement::GotoStatement(_) => false,
+        Statement::IfStatement(ref is) => {
+            expression_calls(&is.condition.child, predicate)
+                || statement_calls(&is.consequence, predicate)
+                || is
+                    .alternative
+                    .as_ref()
+                    .is_some_and(|alternative| statement_calls(&alternative.child, predicate))
+        }
+        Statement::LabeledStatement(ref ls) => block_child_calls(&ls.child, predicate),
+        Statement::ReturnStatement(ref rs) => rs
+            .children
+            .iter()
+            .any(|expr| expression_calls(expr, predicate)),
+        Statement::SwitchStatement(ref ss) => {
+            expression_calls(&ss.condition.child, predicate) || block_calls(&ss.body, predicate)
+        }
+        Statement::WhileStatement(ref ws) => {
+            expression_calls(&ws.condition.child, predicate) || statement_calls(&ws.body, predicate)
+        }
+        Statement::BreakStatement(_) | Statement::ContinueStatement(_) => false,
+    }
+}
+
+/// Returns whether any call within `expr` (including itself)
+/// satisfies `predicate`.
+fn expression_calls(
+    expr: &Expression,
+    predicate: &mut impl FnMut(&CallExpression) -> bool,
+) -> bool {
+    match *expr {
+        Expression::AssignmentExpression(ref ae) => {
+            expression_calls(&ae.left, predicate) || expression_calls(&ae.right, predicate)
+        }
+        Expression::BinaryExpression(ref be) => {
+            expression_calls(&be.left, predicate) || expression_calls(&be.right, predicate)
+        }
+        Expression::CallExpression(ref ce) => {
+            predicate(ce)
+                || ce
+                    .arguments
+                    .children
+                    .iter()
+                    .any(|argument| expression_calls(argument, predicate))
+        }
+        Expression::CharLiteral(_)
+        | Expression::StringLiteral(_)
+        | Expression::Identifier(_)
+        | Expression::NumberLiteral(_)
+        | Expression::True(_)
+        | Expression::False(_) => false,
+        Expression::UpdateExpression(ref ue) => expression_calls(&ue.argument, predicate),
+        Expression::UnaryExpression(ref ue) => expression_calls(&ue.argument, predicate),
+        Expression::ParenthesizedExpression(ref pe) => expression_calls(&pe.child, predicate),
+        Expression::SubscriptExpression(ref se) => {
+            expression_calls(&se.argument, predicate) || expression_calls(&se.index, predicate)
+        }
+        Expression::FieldExpression(ref fe) => expression_calls(&fe.argument, predicate),
+        Expression::ConditionalExpression(ref ce) => {
+            expression_calls(&ce.condition, predicate)
+                || expression_calls(&ce.consequence, predicate)
+                || expression_calls(&ce.alternative, predicate)
+        }
+        Expression::CommaExpression(ref ce) => {
+            expression_calls(&ce.left, predicate) || expression_calls(&ce.right, predicate)
+        }
+    }
+}
+
+impl Codegen {
+    /// Generates `main` (which must be dispatched) and every dispatched
+    /// function it calls as one dispatch loop, with `main`'s frame at
+    /// the stack top.
+    pub(super) fn dispatched_main(&mut self, main: &Function, root: &Environment) -> CodegenResult {
+        let base = self.stack_pointer;
+        let prelude = mem::take(&mut self.output);
+
+        let entry = self.entry(&main.name.src);
+
+        while let Some(name) = self.dispatch.pending.pop() {
+            let function = &root.functions[&name];
+            let block = self.dispatch.entries[&name];
+
+            // The other functions were already checked where they
+            // were defined, so any problems within them have been
+            // reported.
+            let reported = self.diagnostics.len();
+            let result = self.dispatched_function(function, block, root);
+
+            if name != main.name.src {
+                self.diagnostics.truncate(reported);
+            }
+
+            result?;
+        }
+
+        let Dispatch { blocks, .. } = mem::take(&mut self.dispatch);
+        self.output = prelude;
+        self.stack_pointer = base;
+
+        if blocks.len() > u8::MAX.into() {
+            return Err(Diagnostic::new(
+                format!(
+                    "program needs {} blocks for its dispatch loop, but at most {} are supported",
+                    blocks.len(),
+                    u8::MAX
+                ),
+                main.name.span,
+            ));
+        }
+
+        // `main` works on a copy of the globals like any other
+        let globals = base + RESULT + main.return_type.size() + 1;
+        let locals = globals + self.globals.size();
+        self.copy_value(0, globals, self.globals.size(), locals);
+
+        self.move_to(base);
+        self.push_n(entry, '+');
+        self.bf_loop(|cg| {
+            for (index, code) in blocks.iter().enumerate() {
+                cg.dispatch_block(index + 1, code);
+            }
+        });
+
+        self.take_back_globals(globals, 0);
+
+        // nothing to hand the return value to
+        for cell in base + RESULT..base + RESULT + main.return_type.size() {
+            self.move_to(cell);
+            self.push_str("[-]");
+        }
+        self.move_to(base);
+
+        Ok(())
+    }
+
+    /// Generates the blocks of a dispatched function only to report
+    /// any problems within it, throwing them away afterwards.
+    pub(super) fn check_dispatched_function(
+        &mut self,
+        function: &Function,
+        root: &Environment,
+    ) -> CodegenResult {
+        let (prelude, stack_top) = (mem::take(&mut self.output), self.stack_pointer);
+
+        let entry = self.new_block();
+        let result = self.dispatched_function(function, entry, root);
+
+        self.dispatch = Dispatch::default();
+        self.output = prelude;
+        self.stack_pointer = stack_top;
+
+        result
+    }
+
+    /// Generates the body of a dispatched function as blocks, starting
+    /// with the block numbered `entry`.
+    fn dispatched_function(
+        &mut self,
+        function: &Function,
+        entry: usize,
+        root: &Environment,
+    ) -> CodegenResult {
+        let ret = RESULT + function.return_type.size();
+        let globals = ret + 1;
+        let locals = globals + self.globals.size();
+
+        let mut frame_env = Environment::new(Some(root), locals);
+        frame_env.globals_base = Some(globals);
+        frame_env.frame = Some(Frame {
+            result: RESULT,
+            return_type: function.return_type.clone(),
+            exit: Return::Dispatched { ret, locals },
+        });
+
+        let mut location = locals;
+        for &(param, ref r#type) in &function.parameters {
+            frame_env
+                .variables
+                .insert(param.src.clone(), (location, r#type.clone()));
+            location += r#type.size();
+        }
+
+        self.start_block(entry, CONTROL);
+        self.move_to(location);
+
+        self.function_block(&function.definition.body, &frame_env)?;
+
+        self.clear_environment(frame_env);
+        self.return_to_caller(ret, locals);
+
+        Ok(())
+    }
+
+    /// Generates the part of the dispatch loop that runs `code` (the
+    /// block numbered `number`) if the control cell under the head
+    /// holds `number`.
+    fn dispatch_block(&mut self, number: usize, code: &str) {
+        let control = self.stack_pointer;
+        let (difference, flag) = (control + 1, control + 2);
+
+        // the flag ends up set if the difference is zero
+        self.copy_value(control, difference, 1, flag);
+        self.move_to(difference);
+        self.push_n(number, '-');
+        self.move_to(flag);
+        self.push('+');
+        self.move_to(difference);
+        self.bf_loop(|cg| {
+            cg.push_str("[-]");
+            cg.move_to(flag);
+            cg.push('-');
+            cg.move_to(difference);
+        });
+
+        self.move_to(flag);
+        self.bf_loop(|cg| {
+            cg.push('-');
+            cg.move_to(control);
+            cg.push_str("[-]");
+
+            // The block ends on some control cell, with zeroes above
+            // it, which might as well be this one.
+            cg.push_str(code);
+
+            cg.move_to(flag);
+        });
+
+        self.move_to(control);
+    }
+
+    /// Returns the number of the first block of the function called
+    /// `name`, queuing the function up to be generated if need be.
+    fn entry(&mut self, name: &str) -> usize {
+        if let Some(&entry) = self.dispatch.entries.get(name) {
+            return entry;
+        }
+
+        let entry = self.new_block();
+        self.dispatch.entries.insert(name.to_string(), entry);
+        self.dispatch.pending.push(name.to_string());
+
+        entry
+    }
+
+    /// Reserves a number for a new block, to be started later.
+    pub(super) fn new_block(&mut self) -> usize {
+        self.dispatch.blocks.push(String::new());
+
+        self.dispatch.blocks.len()
+    }
+
+    /// Starts generating the block numbered `block`, which begins with
+    /// the head at `head`.
+    fn start_block(&mut self, block: usize, head: usize) {
+        // whatever came since the last block ended can never run
+        self.output.clear();
+
+        self.dispatch.current = Some(block);
+        self.stack_pointer = head;
+    }
+
+    /// Finishes the current block, which must've left the head on a
+    /// control cell. Whatever gets generated until the next block
+    /// starts can never run, so it carries on as though the stack top
+    /// were still at `top`.
+    fn end_block(&mut self, top: usize) {
+        debug_assert_eq!(
+            self.output.matches('[').count(),
+            self.output.matches(']').count(),
+            "Block ended inside a loop"
+        );
+
+        let code = mem::take(&mut self.output);
+
+        if let Some(block) = self.dispatch.current.take() {
+            self.dispatch.blocks[block - 1] = code;
+        }
+
+        self.stack_pointer = top;
+    }
+
+    /// Ends the current block, jumping to the block numbered `target`.
+    pub(super) fn jump(&mut self, target: usize) {
+        let top = self.stack_pointer;
+
+        self.move_to(CONTROL);
+        self.push_n(target, '+');
+
+        self.end_block(top);
+    }
+
+    /// Ends the current block, falling through into the block numbered
+    /// `block`, which starts right away with the same stack top.
+    pub(super) fn fall_through(&mut self, block: usize) {
+        let top = self.stack_pointer;
+
+        self.jump(block);
+
+        self.start_block(block, CONTROL);
+        self.move_to(top);
+    }
+
+    /// Ends the current block, jumping to the block numbered `then` if
+    /// the cell at `condition` is nonzero and `otherwise` if it's
+    /// zero. The cell gets cleared.
+    fn branch(&mut self, condition: usize, then: usize, otherwise: usize) {
+        self.move_to(CONTROL);
+        self.push_n(otherwise, '+');
+
+        self.move_to(condition);
+        self.bf_loop(|cg| {
+            cg.push_str("[-]");
+
+            cg.move_to(CONTROL);
+            if then > otherwise {
+                cg.push_n(then - otherwise, '+');
+            } else {
+                cg.push_n(otherwise - then, '-');
+            }
+
+            cg.move_to(condition);
+        });
+
+        self.move_to(CONTROL);
+
+        self.end_block(condition);
+    }
+
+    /// Ends the current block, handing control back to the caller,
+    /// whose block number is at `ret`. Everything but the return value
+    /// must've been cleared.
+    pub(super) fn return_to_caller(&mut self, ret: usize, top: usize) {
+        self.move_value(ret, &[CONTROL], 1);
+        self.move_to(CONTROL);
+
+        self.end_block(top);
+    }
+
+    /// Calls a dispatched function from within another, pushing its
+    /// return value onto stack.
+    pub(super) fn dispatched_call(
+        &mut self,
+        function: &Function,
+        arguments: &[Expression],
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let frame = self.stack_pointer;
+        let size = function.return_type.size();
+        let ret = frame + RESULT + size;
+        let globals = ret + 1;
+
+        // the arguments go straight into the parameters
+        self.move_to(globals + self.globals.size());
+        for (argument, (_, r#type)) in arguments.iter().zip(&function.parameters) {
+            self.expression_as(argument, r#type.clone(), env)?;
+        }
+
+        // the callee works on its own copy of the globals
+        let top = self.stack_pointer;
+        self.copy_value(env.globals_base(), globals, self.globals.size(), top);
+
+        let continuation = self.new_block();
+        let entry = self.entry(&function.name.src);
+
+        self.move_to(ret);
+        self.push_n(continuation, '+');
+        self.move_to(frame + CONTROL);
+        self.push_n(entry, '+');
+
+        self.end_block(frame);
+        self.start_block(continuation, frame + CONTROL);
+
+        // the callee left nothing but its return value (and the
+        // globals) behind
+        self.take_back_globals(globals, env.globals_base());
+        self.move_value(frame + RESULT, &[frame], size);
+        self.move_to(frame + size);
+
+        Ok(())
+    }
+
+    /// Generates an `if` statement that has to be split into blocks
+    /// (see `splits`), branching to a block for each side.
+    pub(super) fn dispatched_if_statement(
+        &mut self,
+        node: &IfStatement,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let top = self.stack_pointer;
+        self.condition(&node.condition.child, env)?;
+
+        let (consequence, join) = (self.new_block(), self.new_block());
+        let alternative = node
+            .alternative
+            .as_ref()
+            .map(|alternative| (alternative, self.new_block()));
+
+        self.branch(
+            top,
+            consequence,
+            alternative.map_or(join, |(_, block)| block),
+        );
+
+        self.start_block(consequence, CONTROL);
+        self.move_to(top);
+        let result = self.statement(&node.consequence, env);
+        self.recover(result, top);
+        self.jump(join);
+
+        if let Some((alternative, block)) = alternative {
+            self.start_block(block, CONTROL);
+            self.move_to(top);
+            let result = self.statement(&alternative.child, env);
+            self.recover(result, top);
+            self.jump(join);
+        }
+
+        self.start_block(join, CONTROL);
+        self.move_to(top);
+
+        Ok(())
+    }
+
+    /// Generates a `while` statement that has to be split into blocks
+    /// (see `splits`), jumping back to the condition's block after
+    /// every iteration.
+    pub(super) fn dispatched_while_statement(
+        &mut self,
+        node: &WhileStatement,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        self.dispatched_loop(Some(&node.condition.child), None, &node.body, false, env)
+    }
+
+    /// Generates a `do` statement that has to be split into blocks
+    /// (see `splits`), which starts off with its body's block.
+    pub(super) fn dispatched_do_statement(
+        &mut self,
+        node: &DoStatement,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        self.dispatched_loop(Some(&node.condition.child), None, &node.body, true, env)
+    }
+
+    /// Generates a `for` statement that has to be split into blocks
+    /// (see `splits`).
+    pub(super) fn dispatched_for_statement(
+        &mut self,
+        node: &ForStatement,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let mut outer_env = Environment::new(Some(env), self.stack_pointer);
+        self.for_initializer(node, &mut outer_env)?;
+
+        self.dispatched_loop(
+            node.condition.as_deref(),
+            node.update.as_deref(),
+            &node.body,
+            false,
+            &outer_env,
+        )?;
+
+        self.clear_environment(outer_env);
+
+        Ok(())
+    }
+
+    /// Generates a loop as blocks for the condition (which holds if
+    /// there is none), and for the body followed by the update. The
+    /// update gets a block of its own if `continue` has to jump to it.
+    /// With `body_first`, the body runs before the condition is first
+    /// checked.
+    fn dispatched_loop(
+        &mut self,
+        condition: Option<&Expression>,
+        update: Option<&Expression>,
+        body: &Statement,
+        body_first: bool,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let top = self.stack_pointer;
+        let (check, body_block, exit) = (self.new_block(), self.new_block(), self.new_block());
+        let next = match update {
+            Some(_) if may_jump(body) => self.new_block(),
+            _ => check,
+        };
+
+        let mut loop_env = Environment::new(Some(env), top);
+        loop_env.r#loop = Some(Loop::Dispatched { top, exit, next });
+
+        self.jump(if body_first { body_block } else { check });
+
+        self.start_block(check, CONTROL);
+        self.move_to(top);
+        self.loop_condition(condition, None, env)?;
+        self.branch(top, body_block, exit);
+
+        self.start_block(body_block, CONTROL);
+        self.move_to(top);
+        let result = self.statement(body, &loop_env);
+        self.recover(result, top);
+        if next != check {
+            self.jump(next);
+
+            self.start_block(next, CONTROL);
+            self.move_to(top);
+        }
+        if let Some(update) = update {
+            self.discard(update, env)?;
+        }
+        self.jump(check);
+
+        self.start_block(exit, CONTROL);
+        self.move_to(top);
+
+        Ok(())
+    }
+
+    /// Generates a `switch` statement that has to be split into blocks
+    /// (see `splits`), with a block starting at each label. The label's
+    /// position (see `switch_entry`) picks the block to jump to.
+    pub(super) fn dispatched_switch_statement(
+        &mut self,
+        node: &SwitchStatement,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let top = self.stack_pointer;
+        self.switch_entry(node, env)?;
+
+        // one block per label, numbered in order
+        let first = self.dispatch.blocks.len() + 1;
+        for _ in switch_labels(node) {
+            self.new_block();
+        }
+        let exit = self.new_block();
+
+        // the position picks its label's block, with none meaning `exit`
+        self.move_to(CONTROL);
+        self.push_n(exit, '+');
+        self.move_to(top);
+        self.bf_loop(|cg| {
+            cg.move_to(CONTROL);
+            cg.push_n(exit - (first - 1), '-');
+            cg.move_value(top, &[CONTROL], 1);
+            cg.move_to(top);
+        });
+        self.move_to(CONTROL);
+        self.end_block(top);
+
+        let mut switch_env = Environment::new(Some(env), top);
+        switch_env.r#loop = Some(Loop::DispatchedSwitch { top, exit });
+
+        // each label gets fallen through into from the one before
+        self.switch_body(node, &mut switch_env, None, |cg, position| {
+            cg.fall_through(first + position - 1);
+        });
+
+        self.clear_environment(switch_env);
+        self.jump(exit);
+
+        self.start_block(exit, CONTROL);
+        self.move_to(top);
+
+        Ok(())
+    }
+
+    /// Evaluates a `&&` or `||` expression whose right operand has to
+    /// be split into blocks (see `expression_splits`), branching to a
+    /// block for it only if the left operand doesn't decide the result.
+    pub(super) fn dispatched_logical_expression(
+        &mut self,
+        node: &BinaryExpression,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let and = matches!(*node.operator, BinaryOperator::LogicalAnd(_));
+
+        // `||` holds unless the right operand turns out not to
+        let result = self.stack_pointer;
+        if !and {
+            self.push('+');
+        }
+        self.move_head(1);
+
+        let left = self.stack_pointer;
+        self.condition(&node.left, env)?;
+
+        let (right, join) = (self.new_block(), self.new_block());
+        if and {
+            self.branch(left, right, join);
+        } else {
+            self.branch(left, join, right);
+        }
+
+        self.start_block(right, CONTROL);
+        self.move_to(result);
+        if !and {
+            self.push('-');
+        }
+
+        // the right operand decides
+        self.move_to(left);
+        self.condition(&node.right, env)?;
+        self.move_head(-1);
+        self.bf_loop(|cg| {
+            cg.push_str("[-]");
+            cg.move_to(result);
+            cg.push('+');
+            cg.move_to(left);
+        });
+        self.jump(join);
+
+        self.start_block(join, CONTROL);
+        self.move_to(left);
+
+        Ok(())
+    }
+
+    /// Evaluates a conditional (`?:`) expression with a branch that
+    /// has to be split into blocks (see `expression_splits`), branching
+    /// to a block for each of them.
+    pub(super) fn dispatched_conditional_expression(
+        &mut self,
+        node: &ConditionalExpression,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let r#type = ValueType::from_conditional_expression(node, env)?;
+        let size = r#type.size();
+
+        let result = self.stack_pointer;
+        let cond = result + size;
+        self.move_to(cond);
+        self.condition(&node.condition, env)?;
+
+        let (consequence, alternative, join) =
+            (self.new_block(), self.new_block(), self.new_block());
+        self.branch(cond, consequence, alternative);
+
+        for (block, branch) in [
+            (consequence, &node.consequence),
+            (alternative, &node.alternative),
+        ] {
+            self.start_block(block, CONTROL);
+            self.move_to(cond);
+            self.expression_as(branch, r#type.clone(), env)?;
+            self.move_value(cond, &[result], size);
+            self.jump(join);
+        }
+
+        self.start_block(join, CONTROL);
+        self.move_to(cond);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+//! `enum` types, which are just `int`s whose enumerators are named
+//! constants (much like in C itself).
+
+use crate::{diagnostic::Diagnostic, treesitter_wrapper::*};
+
+use super::{CodegenResult, Environment, ValueType, constant_value};
+
+impl ValueType {
+    /// Returns the type that an enum specifier occurring within `env`
+    /// refers to, which is always `int` (as long as the `enum` exists).
+    pub(super) fn from_enum_specifier(
+        spec: &EnumSpecifier,
+        env: &Environment,
+    ) -> CodegenResult<Self> {
+        if let (Some(name), None) = (&spec.name, &spec.body)
+            && !env.has_enum(&name.src)
+        {
+            return Err(Diagnostic::new(
+                format!("unknown enum `{}`", name.src),
+                name.span,
+            ));
+        }
+
+        Ok(Self::Int)
+    }
+}
+
+impl Environment<'_> {
+    /// Returns whether there is an `enum` with the tag `name`.
+    fn has_enum(&self, name: &str) -> bool {
+        self.enums.contains(name) || self.parent.is_some_and(|parent| parent.has_enum(name))
+    }
+
+    /// Returns the value of the enumerator called `name`, unless it's
+    /// shadowed by a variable (or there is no such enumerator).
+    pub(super) fn lookup_constant(&self, name: &str) -> Option<i64> {
+        if self.variables.contains_key(name) || self.globals.contains_key(name) {
+            return None;
+        }
+
+        self.constants
+            .get(name)
+            .copied()
+            .or_else(|| self.parent.and_then(|parent| parent.lookup_constant(name)))
+    }
+
+    /// Adds the `enum` that `spec` defines (if it has a body) to the
+    /// environment, along with each of its enumerators. Those without
+    /// a value get the one after the previous enumerator's (or `0`).
+    pub(super) fn add_enum(&mut self, spec: &EnumSpecifier) -> CodegenResult {
+        let Some(ref body) = spec.body else {
+            return Ok(());
+        };
+
+        if let Some(ref name) = spec.name
+            && !self.enums.insert(name.src.clone())
+        {
+            return Err(Diagnostic::new(
+                format!("redefinition of `enum {}`", name.src),
+                name.span,
+            ));
+        }
+
+        let mut next = 0;
+
+        for enumerator in &body.children {
+            let name = &enumerator.name;
+
+            let value = match enumerator.value {
+                Some(ref value) => constant_value(value, self).ok_or_else(|| {
+                    Diagnostic::new("enumerator value must be a constant", value.span())
+                })?,
+                None => next,
+            };
+
+            let bits = 8 * ValueType::Int.size() - 1;
+
+            if !(-(1 << bits)..1 << bits).contains(&value) {
+                return Err(Diagnostic::new(
+                    format!("enumerator value {value} is out of range for `int`"),
+                    enumerator.span,
+                ));
+            }
+
+            if self.variables.contains_key(&name.src)
+                || self.globals.contains_key(&name.src)
+                || self.constants.insert(name.src.clone(), value).is_some()
+            {
+                return Err(Diagnostic::new(
+                    format!("redeclaration of `{}`", name.src),
+                    name.span,
+                ));
+            }
+
+            next = value + 1;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+//! Global variables and `static` locals, which have static storage:
+//! they live in a region at the start of the tape, before the stack,
+//! and get their (constant) initial values before anything else runs.
+//!
+//! The stack begins right after the region, so it gets laid out by a
+//! pass over the whole program before anything is generated (see
+//! `Codegen::lay_out_globals`).
+//!
+//! Dispatched functions never know where their frame is, so they can't
+//! reach the region. Each of their frames holds a copy of it instead,
+//! which the caller hands over on every call and takes back afterwards
+//! (see `dispatch`).
+
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
+
+use crate::{diagnostic::Diagnostic, treesitter_wrapper::*};
+
+use super::{
+    Codegen, CodegenResult, Environment, ValueType, array::Rail, constant_value,
+    interpret_declaration,
+};
+
+/// The region of the tape holding every variable with static storage.
+#[derive(Default)]
+pub(super) struct Globals {
+    /// Location of each variable within the region, by where its name
+    /// is declared (so that a `static` local keeps its place however
+    /// many times its function gets inlined).
+    locations: HashMap<usize, usize>,
+    /// Size of the region laid out so far.
+    size: usize,
+    /// The variables (by where their names are declared) that have
+    /// been initialized already.
+    initialized: HashSet<usize>,
+    /// Code giving each variable its initial value, which runs before
+    /// anything else.
+    init: String,
+    /// Where `init` leaves the head.
+    head: usize,
+}
+
+impl Globals {
+    /// Returns the size of the region laid out so far.
+    pub(super) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the location within the region of the variable `name`
+    /// (of type `r#type`), laying it out at the end if it isn't yet.
+    fn location(&mut self, name: &Identifier, r#type: &ValueType) -> usize {
+        if let Some(&location) = self.locations.get(&name.span.start_byte) {
+            return location;
+        }
+
+        let location = self.size + Rail::of(r#type).map_or(0, |rail| rail.size());
+        self.size = location + r#type.size();
+        self.locations.insert(name.span.start_byte, location);
+
+        location
+    }
+
+    /// Lays out the variable that `decl` (occurring within `env`)
+    /// declares, unless there's anything wrong with it.
+    fn lay_out(&mut self, decl: &Declaration, env: &Environment) {
+        if let Ok((name, r#type)) = interpret_declaration(decl, env) {
+            self.location(name, &r#type);
+        }
+    }
+
+    /// Lays out the `static` locals declared by `child`, or within
+    /// it, adding any types it declares to `env` (its scope).
+    fn lay_out_child(&mut self, child: &BlockChild, env: &mut Environment) {
+        // anything wrong here gets reported when it's generated
+        match *child {
+            BlockChild::Declaration(ref decl) => {
+                _ = env.add_tag_from_specifier(&decl.r#type);
+
+                if let Ok(true) = is_static(decl) {
+                    self.lay_out(decl, env);
+                }
+            }
+            BlockChild::TypeDefinition(ref typedef) => _ = env.add_type_from_node(typedef),
+            BlockChild::StructSpecifier(ref spec) => _ = env.add_struct(spec),
+            BlockChild::EnumSpecifier(ref spec) => _ = env.add_enum(spec),
+            BlockChild::Statement(ref stmt) => self.lay_out_statement(stmt, env),
+        }
+    }
+
+    /// Lays out the `static` locals declared within `stmt`, which
+    /// occurs within `env`.
+    fn lay_out_statement(&mut self, stmt: &Statement, env: &Environment) {
+        match *stmt {
+            Statement::CompoundStatement(ref cs) => {
+                let mut block_env = Environment::new(Some(env), 0);
+
+                for child in &cs.children {
+                    self.lay_out_child(child, &mut block_env);
+                }
+            }
+            Statement::CaseStatement(ref cs) => {
+                let mut case_env = Environment::new(Some(env), 0);
+
+                for child in &cs.children {
+                    self.lay_out_child(child, &mut case_env);
+                }
+            }
+            Statement::LabeledStatement(ref ls) => {
+                self.lay_out_child(&ls.child, &mut Environment::new(Some(env), 0));
+            }
+            Statement::DoStatement(ref ds) => self.lay_out_statement(&ds.body, env),
+            Statement::ForStatement(ref fs) => self.lay_out_statement(&fs.body, env),
+            Statement::WhileStatement(ref ws) => self.lay_out_statement(&ws.body, env),
+            Statement::SwitchStatement(ref ss) => {
+                for child in &ss.body.children {
+                    self.lay_out_child(child, &mut Environment::new(Some(env), 0));
+                }
+            }
+            Statement::IfStatement(ref is) => {
+                self.lay_out_statement(&is.consequence, env);
+
+                if let Some(ref alternative) = is.alternative {
+                    self.lay_out_statement(&alternative.child, env);
+                }
+            }
+            Statement::ExpressionStatement(_)
+            | Statement::GotoStatement(_)
+            | Statement::ReturnStatement(_)
+            | Statement::BreakStatement(_)
+            | Statement::ContinueStatement(_) => {}
+        }
+    }
+}
+
+/// Returns whether `decl` is `static`, failing on any storage class
+/// that isn't supported.
+pub(super) fn is_static(decl: &Declaration) -> CodegenResult<bool> {
+    let mut r#static = false;
+
+    for child in &decl.children {
+        if let DeclarationSpecifier::StorageClassSpecifier(ref spec) = *child {
+            match spec.src.as_str() {
+                "static" => r#static = true,
+                // these change nothing here
+                "auto" | "register" => {}
+                other => {
+                    return Err(Diagnostic::new(
+                        format!("storage class `{other}` is not supported"),
+                        spec.span,
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(r#static)
+}
+
+/// Fails unless `initializer` (of the variable `name`) holds nothing
+/// but constants, as it must for a variable with static storage.
+fn check_constant(
+    initializer: &Initializer,
+    name: &Identifier,
+    env: &Environment,
+) -> CodegenResult {
+    match *initializer {
+        Initializer::InitializerList(ref list) => list
+            .children
+            .iter()
+            .try_for_each(|initializer| check_constant(initializer, name, env)),
+        // a string literal is as good as a list of `char` constants
+        Initializer::Expression(ref value) if matches!(**value, Expression::StringLiteral(_)) => {
+            Ok(())
+        }
+        Initializer::Expression(ref value) => {
+            // an ill-typed expression gets a better error than this
+            ValueType::from_expression(value, env)?;
+
+            match constant_value(value, env) {
+                Some(_) => Ok(()),
+                None => Err(Diagnostic::new(
+                    format!("initializer of `{}` must be a constant", name.src),
+                    value.span(),
+                )),
+            }
+        }
+    }
+}
+
+impl Environment<'_> {
+    /// Returns the location of the globals region, or of the copy of
+    /// it that the dispatched function `self` is within works on.
+    pub(super) fn globals_base(&self) -> usize {
+        self.globals_base
+            .unwrap_or_else(|| self.parent.map_or(0, |parent| parent.globals_base()))
+    }
+}
+
+impl Codegen {
+    /// Lays out the globals region: every variable declared at the top
+    /// level of `root`, and every `static` local in its functions.
+    ///
+    /// This goes through the declarations in order the same way
+    /// generating them does, so each type comes out the same (anything
+    /// wrong is left for then to report).
+    pub(super) fn lay_out_globals(&mut self, root: &TranslationUnit) {
+        let mut env = Environment::new(None, 0);
+
+        for child in &root.children {
+            match *child {
+                TUChildren::Declaration(ref decl) => {
+                    _ = env.add_tag_from_specifier(&decl.r#type);
+                    self.globals.lay_out(decl, &env);
+                }
+                TUChildren::FunctionDefinition(ref funcdef) => {
+                    let mut body_env = Environment::new(Some(&env), 0);
+
+                    for child in &funcdef.body.children {
+                        self.globals.lay_out_child(child, &mut body_env);
+                    }
+                }
+                TUChildren::TypeDefinition(ref typedef) => _ = env.add_type_from_node(typedef),
+                TUChildren::StructSpecifier(ref spec) => _ = env.add_struct(spec),
+                TUChildren::EnumSpecifier(ref spec) => _ = env.add_enum(spec),
+            }
+        }
+    }
+
+    /// Adds the variable with static storage that `decl` declares (as
+    /// `name`, of type `r#type`) to `env`, initializing it the first
+    /// time it comes up.
+    pub(super) fn add_global(
+        &mut self,
+        env: &mut Environment,
+        decl: &Declaration,
+        name: &Identifier,
+        r#type: ValueType,
+    ) -> CodegenResult {
+        // it was laid out beforehand, unless its type only worked out
+        // here (which means a problem with it was reported already)
+        let location = self.globals.location(name, &r#type);
+        env.globals
+            .insert(name.src.clone(), (location, r#type.clone()));
+
+        if !self.globals.initialized.insert(name.span.start_byte) {
+            return Ok(());
+        }
+
+        let Declarator::InitDeclarator(ref init) = *decl.declarator else {
+            return Ok(());
+        };
+
+        check_constant(&init.value, name, env)?;
+
+        // The initializer runs from the end of the region so far, where
+        // the head is then left for the next one.
+        let output = mem::replace(&mut self.output, mem::take(&mut self.globals.init));
+        let stack_top = mem::replace(&mut self.stack_pointer, self.globals.head);
+        self.move_to(self.globals.size);
+
+        let result = self.initialize(init, name, &r#type, location, env);
+
+        self.globals.head = self.stack_pointer;
+        self.globals.init = mem::replace(&mut self.output, output);
+        self.stack_pointer = stack_top;
+
+        result
+    }
+
+    /// Puts the code initializing the globals in front of everything
+    /// generated, leaving the head at `stack_base` for the rest.
+    pub(super) fn globals_prelude(&mut self, stack_base: usize) {
+        let program = mem::replace(&mut self.output, mem::take(&mut self.globals.init));
+
+        self.stack_pointer = self.globals.head;
+        self.move_to(stack_base);
+
+        self.output.push_str(&program);
+    }
+
+    /// Moves the copy of the globals region at `copy` back onto the
+    /// region (or the other copy) at `destination`, replacing whatever
+    /// it held.
+    pub(super) fn take_back_globals(&mut self, copy: usize, destination: usize) {
+        let size = self.globals.size();
+
+        for cell in destination..destination + size {
+            self.move_to(cell);
+            self.push_str("[-]");
+        }
+
+        self.move_value(copy, &[destination], size);
+    }
+}
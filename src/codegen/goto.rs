@@ -0,0 +1,410 @@
+//! `goto` statements, whose labels have to be directly within the body
+//! of the function (in between its other statements).
+//!
+//! BF only has loops to jump with, so the body of a function with
+//! labels gets lowered into a state machine: a loop that picks the label
+//! to carry on from on every iteration, much like a `switch` on the
+//! label's number (see `Goto`). Within a dispatched function, each label
+//! starts a block of its own instead.
+//!
+//! Either way, every local of the body gets its space up front, so that
+//! the stack looks the same at every label.
+
+use std::{collections::HashMap, mem, slice};
+
+use crate::{diagnostic::Diagnostic, treesitter_wrapper::*};
+
+use super::{
+    Codegen, CodegenResult, Environment, Goto, ValueType, dispatch::dispatched, globals::is_static,
+    interpret_declaration,
+};
+
+/// A child of a function's body, with any labels in front of it taken
+/// apart from it.
+enum Item<'a> {
+    Label(&'a StatementIdentifier),
+    Child(&'a BlockChild),
+}
+
+/// Returns the children of `body` and the labels in between them, in
+/// order.
+fn items(body: &CompoundStatement) -> Vec<Item<'_>> {
+    let mut items = vec![];
+
+    for mut child in &body.children {
+        while let BlockChild::Statement(ref stmt) = *child
+            && let Statement::LabeledStatement(ref ls) = **stmt
+        {
+            items.push(Item::Label(&ls.label));
+            child = &*ls.child;
+        }
+
+        items.push(Item::Child(child));
+    }
+
+    items
+}
+
+/// Adds every `goto` statement within `stmt` to `found`.
+fn gotos<'a>(stmt: &'a Statement, found: &mut Vec<&'a GotoStatement>) {
+    let children = |children: &'a [BlockChild], found: &mut Vec<_>| {
+        for child in children {
+            if let BlockChild::Statement(ref stmt) = *child {
+                gotos(stmt, found);
+            }
+        }
+    };
+
+    match *stmt {
+        Statement::CaseStatement(ref cs) => children(&cs.children, found),
+        Statement::CompoundStatement(ref cs) => children(&cs.children, found),
+        Statement::DoStatement(ref ds) => gotos(&ds.body, found),
+        Statement::ForStatement(ref fs) => gotos(&fs.body, found),
+        Statement::GotoStatement(ref gs) => found.push(gs),
+        Statement::IfStatement(ref is) => {
+            gotos(&is.consequence, found);
+
+            if let Some(alternative) = &is.alternative {
+                gotos(&alternative.child, found);
+            }
+        }
+        Statement::LabeledStatement(ref ls) => children(slice::from_ref(&*ls.child), found),
+        Statement::SwitchStatement(ref ss) => children(&ss.body.children, found),
+        Statement::WhileStatement(ref ws) => gotos(&ws.body, found),
+        Statement::ExpressionStatement(_)
+        | Statement::ReturnStatement(_)
+        | Statement::BreakStatement(_)
+        | Statement::ContinueStatement(_) => {}
+    }
+}
+
+/// Returns where each label is among `items`, failing if one is defined
+/// more than once.
+fn label_positions<'a>(items: &[Item<'a>]) -> CodegenResult<HashMap<&'a str, usize>> {
+    let mut positions = HashMap::new();
+
+    for (position, item) in items.iter().enumerate() {
+        if let Item::Label(label) = *item
+            && positions.insert(label.src.as_str(), position).is_some()
+        {
+            return Err(Diagnostic::new(
+                format!("redefinition of label `{}`", label.src),
+                label.span,
+            ));
+        }
+    }
+
+    Ok(positions)
+}
+
+/// Fails if a `goto` jumps forward past the declaration of a local
+/// with an initializer, into its scope (which C allows, but would leave
+/// the local without its value).
+fn check_skipped_initializers(
+    items: &[Item],
+    positions: &HashMap<&str, usize>,
+    env: &Environment,
+) -> CodegenResult {
+    for (from, item) in items.iter().enumerate() {
+        let Item::Child(BlockChild::Statement(stmt)) = *item else {
+            continue;
+        };
+
+        let mut found = vec![];
+        gotos(stmt, &mut found);
+
+        for goto in found {
+            let Some(&to) = positions.get(goto.label.src.as_str()) else {
+                continue;
+            };
+
+            let skipped =
+                items
+                    .get(from + 1..to)
+                    .into_iter()
+                    .flatten()
+                    .find_map(|item| match *item {
+                        Item::Child(BlockChild::Declaration(decl))
+                            if matches!(*decl.declarator, Declarator::InitDeclarator(_))
+                                && !matches!(is_static(decl), Ok(true)) =>
+                        {
+                            Some(decl)
+                        }
+                        _ => None,
+                    });
+
+            if let Some(decl) = skipped {
+                let name = interpret_declaration(decl, env).map_or_else(
+                    |_| "a local".to_string(),
+                    |(name, _)| format!("`{}`", name.src),
+                );
+
+                return Err(Diagnostic::new(
+                    format!(
+                        "jump to label `{}` skips the initialization of {name}",
+                        goto.label.src
+                    ),
+                    goto.span,
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl Codegen {
+    /// Generates the body of a function (within its frame's
+    /// environment), as a state machine if it has any labels.
+    ///
+    /// Problems within the body are recorded rather than returned, so
+    /// this never fails itself.
+    pub(super) fn function_block(
+        &mut self,
+        body: &CompoundStatement,
+        env: &Environment,
+    ) -> CodegenResult {
+        let items = items(body);
+
+        if !items.iter().any(|item| matches!(*item, Item::Label(_))) {
+            return self.compound_statement(body, env);
+        }
+
+        let stack_top = self.stack_pointer;
+        let result = self.labeled_function_block(&items, env);
+        self.recover(result, stack_top);
+
+        Ok(())
+    }
+
+    /// Generates the body of a function with labels, given its `items`.
+    fn labeled_function_block(&mut self, items: &[Item], env: &Environment) -> CodegenResult {
+        let positions = label_positions(items)?;
+        let mut body_env = Environment::new(Some(env), self.stack_pointer);
+
+        for item in items {
+            if let Item::Child(child) = *item {
+                self.reserve(child, &mut body_env);
+            }
+        }
+
+        // the locals only come into scope where they're declared
+        let mut reserved = mem::take(&mut body_env.variables);
+
+        check_skipped_initializers(items, &positions, &body_env)?;
+
+        if dispatched(env) {
+            self.dispatched_labeled_body(items, &mut body_env, &mut reserved);
+        } else {
+            self.labeled_body(items, &mut body_env, &mut reserved)?;
+        }
+
+        self.clear_environment(body_env);
+
+        Ok(())
+    }
+
+    /// Reserves space for `child` if it's a declaration, or otherwise
+    /// adds whatever type it defines to `env`.
+    ///
+    /// Problems are recorded rather than returned.
+    fn reserve(&mut self, child: &BlockChild, env: &mut Environment) {
+        let stack_top = self.stack_pointer;
+
+        let result = match *child {
+            BlockChild::Declaration(ref decl) => self.add_variable(env, decl),
+            BlockChild::TypeDefinition(ref typedef) => env.add_type_from_node(typedef),
+            BlockChild::StructSpecifier(ref spec) => env.add_struct(spec),
+            BlockChild::EnumSpecifier(ref spec) => env.add_enum(spec),
+            BlockChild::Statement(_) => Ok(()),
+        };
+
+        self.recover(result, stack_top);
+    }
+
+    /// Generates a child of a function body with labels, which only
+    /// runs if the cell at `gate` (if any) is nonzero. A declaration
+    /// brings its local (out of `reserved`) into scope.
+    ///
+    /// Problems are recorded rather than returned.
+    fn labeled_child(
+        &mut self,
+        child: &BlockChild,
+        env: &mut Environment,
+        reserved: &mut HashMap<String, (usize, ValueType)>,
+        gate: Option<usize>,
+    ) {
+        let BlockChild::Declaration(ref decl) = *child else {
+            // types were already added up front
+            if let BlockChild::Statement(_) = *child {
+                self.block_child(child, env, gate);
+            }
+
+            return;
+        };
+
+        let stack_top = self.stack_pointer;
+
+        // any problem was already reported when reserving it
+        let Some((name, variable)) = interpret_declaration(decl, env)
+            .ok()
+            .and_then(|(name, _)| reserved.remove_entry(&name.src))
+        else {
+            return;
+        };
+
+        env.variables.insert(name, variable);
+
+        let result = self.gate(gate, |cg| cg.declaration(decl, env));
+        self.recover(result, stack_top);
+    }
+
+    /// Generates the body of an inlined function with labels, as a
+    /// loop over the label to carry on from (see `Goto::Gated`). The
+    /// start of the body counts as the label numbered 1.
+    fn labeled_body(
+        &mut self,
+        items: &[Item],
+        env: &mut Environment,
+        reserved: &mut HashMap<String, (usize, ValueType)>,
+    ) -> CodegenResult {
+        let names: Vec<_> = items
+            .iter()
+            .filter_map(|item| match *item {
+                Item::Label(label) => Some(label),
+                Item::Child(_) => None,
+            })
+            .collect();
+
+        // the start of the body takes the first number
+        if let Some(label) = names.get(usize::from(u8::MAX) - 1) {
+            return Err(Diagnostic::new(
+                format!(
+                    "too many labels in one function (at most {} are supported)",
+                    u8::MAX - 1
+                ),
+                label.span,
+            ));
+        }
+
+        let labels = names
+            .into_iter()
+            .map(|label| label.src.clone())
+            .zip(2..)
+            .collect();
+
+        let pc = self.stack_pointer;
+        let (entry, matched) = (pc + 1, pc + 2);
+        env.goto = Some(Goto::Gated {
+            pc,
+            matched,
+            labels,
+        });
+
+        self.push('+');
+        self.move_head(3);
+
+        self.move_to(pc);
+        self.bf_loop(|cg| {
+            cg.move_value(pc, &[entry], 1);
+            cg.move_to(matched + 1);
+            cg.reach_label(entry, matched);
+
+            for item in items {
+                match *item {
+                    Item::Label(_) => cg.reach_label(entry, matched),
+                    Item::Child(child) => cg.labeled_child(child, env, reserved, Some(matched)),
+                }
+            }
+
+            for cell in [matched, entry] {
+                cg.move_to(cell);
+                cg.push_str("[-]");
+            }
+
+            cg.move_to(pc);
+        });
+
+        self.move_to(matched + 1);
+
+        Ok(())
+    }
+
+    /// Generates the body of a dispatched function with labels, each of
+    /// which starts a block (see `Goto::Dispatched`).
+    fn dispatched_labeled_body(
+        &mut self,
+        items: &[Item],
+        env: &mut Environment,
+        reserved: &mut HashMap<String, (usize, ValueType)>,
+    ) {
+        let labels: HashMap<_, _> = items
+            .iter()
+            .filter_map(|item| match *item {
+                Item::Label(label) => Some((label.src.clone(), self.new_block())),
+                Item::Child(_) => None,
+            })
+            .collect();
+
+        env.goto = Some(Goto::Dispatched {
+            top: self.stack_pointer,
+            labels: labels.clone(),
+        });
+
+        for item in items {
+            match *item {
+                Item::Label(label) => self.fall_through(labels[&label.src]),
+                Item::Child(child) => self.labeled_child(child, env, reserved, None),
+            }
+        }
+    }
+
+    /// Generates code for a `goto` statement, which gets to its label
+    /// (see `Goto`).
+    pub(super) fn goto_statement(
+        &mut self,
+        node: &GotoStatement,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let undeclared = || {
+            Diagnostic::new(
+                format!("use of undeclared label `{}`", node.label.src),
+                node.label.span,
+            )
+        };
+
+        let top = self.stack_pointer;
+
+        match *env.goto().ok_or_else(undeclared)? {
+            Goto::Gated { pc, ref labels, .. } => {
+                let number = *labels.get(&node.label.src).ok_or_else(undeclared)?;
+
+                self.move_to(pc);
+                self.push_n(number, '+');
+
+                // which stops everything until the next iteration
+                for cell in env.flags() {
+                    self.move_to(cell);
+                    self.push_str("[-]");
+                }
+
+                self.move_to(top);
+            }
+            Goto::Dispatched {
+                top: base,
+                ref labels,
+            } => {
+                let block = *labels.get(&node.label.src).ok_or_else(undeclared)?;
+
+                for cell in (base..top).rev() {
+                    self.move_to(cell);
+                    self.push_str("[-]");
+                }
+
+                self.jump(block);
+            }
+        }
+
+        Ok(())
+    }
+}
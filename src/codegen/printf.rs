@@ -0,0 +1,479 @@
+//! The `printf` builtin, whose format string gets parsed at compile
+//! time so that only the conversions are left to do at runtime.
+
+use std::mem;
+
+use crate::{diagnostic::Diagnostic, treesitter_wrapper::*};
+
+use super::{Codegen, CodegenResult, Environment, ValueType, array::is_place, string_value};
+
+/// A conversion (e.g., `%d`) within a format string.
+#[derive(Clone, Copy)]
+enum Conversion {
+    /// `%c`, a character.
+    Char,
+    /// `%d` (or `%i`), a signed decimal integer.
+    Signed,
+    /// `%u`, an unsigned decimal integer.
+    Unsigned,
+    /// `%x`, an unsigned hexadecimal integer (in lowercase).
+    Hex,
+    /// `%s`, a string.
+    String,
+}
+
+/// Part of a format string.
+enum Piece {
+    /// Text printed as is.
+    Text(Vec<u8>),
+    /// A conversion of the next argument.
+    Conversion(Conversion),
+}
+
+/// An argument to `printf`, ready to be printed.
+enum Argument {
+    /// Bytes known at compile time (i.e., a string literal).
+    Literal(Vec<u8>),
+    /// A value of type `r#type` pushed onto stack at `location`.
+    Pushed { location: usize, r#type: ValueType },
+}
+
+impl Conversion {
+    /// Returns how the conversion is written, for diagnostics.
+    fn spec(self) -> &'static str {
+        match self {
+            Self::Char => "%c",
+            Self::Signed => "%d",
+            Self::Unsigned => "%u",
+            Self::Hex => "%x",
+            Self::String => "%s",
+        }
+    }
+}
+
+/// Splits the bytes of a format string (whose literal is at `span`)
+/// into text and conversions. As in C, the string ends at the first
+/// zero, if there is one.
+fn parse_format(bytes: &[u8], span: Span) -> CodegenResult<Vec<Piece>> {
+    let mut pieces = vec![];
+    let mut text = vec![];
+    let mut bytes = bytes.iter().take_while(|&&byte| byte != 0);
+
+    while let Some(&byte) = bytes.next() {
+        if byte != b'%' {
+            text.push(byte);
+            continue;
+        }
+
+        let conversion = match bytes.next() {
+            Some(b'%') => {
+                text.push(b'%');
+                continue;
+            }
+            Some(b'c') => Conversion::Char,
+            Some(b'd' | b'i') => Conversion::Signed,
+            Some(b'u') => Conversion::Unsigned,
+            Some(b'x') => Conversion::Hex,
+            Some(b's') => Conversion::String,
+            Some(&other) => {
+                return Err(Diagnostic::new(
+                    format!(
+                        "unsupported conversion `%{}` in format string",
+                        char::from(other)
+                    ),
+                    span,
+                ));
+            }
+            None => {
+                return Err(Diagnostic::new(
+                    "format string ends in the middle of a conversion",
+                    span,
+                ));
+            }
+        };
+
+        if !text.is_empty() {
+            pieces.push(Piece::Text(mem::take(&mut text)));
+        }
+        pieces.push(Piece::Conversion(conversion));
+    }
+
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+
+    Ok(pieces)
+}
+
+/// Returns how many digits it takes to write any `size`-cell unsigned
+/// integer in base `radix`.
+fn digit_count(size: usize, radix: u64) -> usize {
+    let mut max = u64::MAX >> (64 - 8 * size);
+    let mut count = 1;
+
+    while max >= radix {
+        max /= radix;
+        count += 1;
+    }
+
+    count
+}
+
+impl Codegen {
+    /// Evaluates a call to `printf`. Every argument is evaluated
+    /// (left to right) before anything gets printed.
+    pub(super) fn printf(&mut self, node: &CallExpression, env: &Environment<'_>) -> CodegenResult {
+        let Some((format, arguments)) = node.arguments.children.split_first() else {
+            return Err(Diagnostic::new("`printf` takes a format string", node.span));
+        };
+
+        let Expression::StringLiteral(ref sl) = *format else {
+            return Err(Diagnostic::new(
+                "format string of `printf` must be a string literal",
+                format.span(),
+            ));
+        };
+
+        let pieces = parse_format(&string_value(sl)?, sl.span)?;
+        let conversions: Vec<_> = pieces
+            .iter()
+            .filter_map(|piece| match *piece {
+                Piece::Conversion(conversion) => Some(conversion),
+                Piece::Text(_) => None,
+            })
+            .collect();
+
+        if conversions.len() != arguments.len() {
+            return Err(Diagnostic::new(
+                format!(
+                    "format string takes {} argument{} but {} were supplied",
+                    conversions.len(),
+                    if conversions.len() == 1 { "" } else { "s" },
+                    arguments.len(),
+                ),
+                node.span,
+            ));
+        }
+
+        let base = self.stack_pointer;
+        let mut pushed = vec![];
+
+        for (&conversion, argument) in conversions.iter().zip(arguments) {
+            pushed.push(self.printf_argument(conversion, argument, env)?);
+        }
+
+        let top = self.stack_pointer;
+        let mut pushed = pushed.into_iter();
+
+        for piece in &pieces {
+            match *piece {
+                Piece::Text(ref text) => {
+                    self.move_to(top);
+                    self.print_bytes(text);
+                }
+                Piece::Conversion(conversion) => {
+                    let argument = pushed.next().expect("one argument per conversion");
+
+                    self.print_argument(conversion, argument, top);
+                }
+            }
+        }
+
+        self.move_to(base);
+
+        Ok(())
+    }
+
+    /// Evaluates an argument to `printf` for `conversion`, pushing it
+    /// onto stack unless it's known at compile time. Integers smaller
+    /// than an `int` get promoted to one, as in C.
+    fn printf_argument(
+        &mut self,
+        conversion: Conversion,
+        argument: &Expression,
+        env: &Environment<'_>,
+    ) -> CodegenResult<Argument> {
+        let location = self.stack_pointer;
+
+        if let (Conversion::String, Expression::StringLiteral(sl)) = (conversion, argument) {
+            return Ok(Argument::Literal(string_value(sl)?));
+        }
+
+        let r#type = ValueType::from_expression(argument, env)?;
+
+        let r#type = match conversion {
+            // a `bool` is already the one cell holding 0 or 1
+            Conversion::Char if r#type == ValueType::Bool => {
+                self.expression(argument, env)?;
+
+                ValueType::Char
+            }
+            Conversion::Signed | Conversion::Unsigned if r#type == ValueType::Bool => {
+                self.expression(argument, env)?;
+                self.convert(ValueType::Bool, ValueType::Int);
+
+                ValueType::Int
+            }
+            Conversion::Char => {
+                self.expression_as(argument, ValueType::Char, env)?;
+
+                ValueType::Char
+            }
+            Conversion::Signed | Conversion::Unsigned | Conversion::Hex => {
+                if !r#type.is_integer() {
+                    return Err(Diagnostic::new(
+                        format!("`{}` takes an integer, found `{type}`", conversion.spec()),
+                        argument.span(),
+                    ));
+                }
+
+                self.expression(argument, env)?;
+
+                if r#type.size() < ValueType::Int.size() {
+                    self.convert(r#type, ValueType::Int);
+
+                    ValueType::Int
+                } else {
+                    r#type
+                }
+            }
+            Conversion::String => {
+                let ValueType::Array(ref element, length) = r#type else {
+                    return Err(Diagnostic::new(
+                        format!("`%s` takes a string literal or `char` array, found `{type}`"),
+                        argument.span(),
+                    ));
+                };
+
+                if !matches!(**element, ValueType::Char | ValueType::UnsignedChar) {
+                    return Err(Diagnostic::new(
+                        format!("`%s` takes a string literal or `char` array, found `{type}`"),
+                        argument.span(),
+                    ));
+                }
+
+                if !is_place(argument) {
+                    return Err(Diagnostic::new(
+                        "`%s` cannot print a temporary array",
+                        argument.span(),
+                    ));
+                }
+
+                self.push_characters(argument, element, length, env)?;
+
+                r#type
+            }
+        };
+
+        Ok(Argument::Pushed { location, r#type })
+    }
+
+    /// Pushes a copy of the `char` array (with elements of type
+    /// `element`) that the lvalue `expr` refers to, a character at a
+    /// time (since that's all an indexed place can carry).
+    fn push_characters(
+        &mut self,
+        expr: &Expression,
+        element: &ValueType,
+        length: usize,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        let location = self.stack_pointer;
+
+        // Any offset goes where the last character ends up, which
+        // `release` moves it into.
+        self.move_head((length - 1).cast_signed());
+
+        let mut place = self.place(expr, env)?;
+        self.narrow(&mut place, 0, element.clone());
+
+        for i in 0..length {
+            if i > 0 {
+                self.narrow(&mut place, 1, element.clone());
+            }
+
+            let value = self.stack_pointer;
+            self.load(&place);
+
+            if i + 1 < length {
+                self.move_value(value, &[location + i], 1);
+                self.move_to(value);
+            } else {
+                self.release(&place, value);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints an argument that `printf_argument` evaluated, clearing
+    /// it. Everything from `top` up is free to use as scratch.
+    fn print_argument(&mut self, conversion: Conversion, argument: Argument, top: usize) {
+        let (location, r#type) = match argument {
+            Argument::Literal(bytes) => {
+                let text: Vec<_> = bytes.into_iter().take_while(|&byte| byte != 0).collect();
+
+                self.move_to(top);
+                self.print_bytes(&text);
+
+                return;
+            }
+            Argument::Pushed { location, r#type } => (location, r#type),
+        };
+
+        match conversion {
+            Conversion::Char => {
+                self.move_to(location);
+                self.push_str(".[-]");
+            }
+            Conversion::Signed => self.print_integer(location, r#type.size(), 10, true, top),
+            Conversion::Unsigned => self.print_integer(location, r#type.size(), 10, false, top),
+            Conversion::Hex => self.print_integer(location, r#type.size(), 16, false, top),
+            Conversion::String => self.print_characters(location, r#type.size(), top),
+        }
+    }
+
+    /// Prints the `size`-cell integer at `location` in base `radix`
+    /// (which is at most 16), clearing it. A signed integer gets
+    /// printed with a `-` if it's negative.
+    ///
+    /// The digits are worked out least significant first by dividing
+    /// by `radix` for as long as anything's left, each being shifted
+    /// onto the bottom of a row of cells (as its character, which is
+    /// never zero). Then the whole row gets printed, skipping any
+    /// cells never reached (which are still zero).
+    fn print_integer(&mut self, location: usize, size: usize, radix: u8, signed: bool, top: usize) {
+        let count = digit_count(size, radix.into());
+        let digits = top;
+        let value = digits + count;
+
+        self.move_value(location, &[value], size);
+
+        if signed {
+            let negative = value + size;
+
+            self.sign_bit(value + size - 1, negative, negative + 1);
+            self.if_zero(
+                negative,
+                negative + 1,
+                |_| {},
+                |cg| {
+                    cg.move_to(negative + 2);
+                    cg.print_bytes(b"-");
+                    cg.negate(value, size, negative + 2);
+                },
+            );
+
+            self.move_to(negative);
+            self.push_str("[-]");
+        }
+
+        // Holds whether there are digits left (there's always at
+        // least one), then the divisor, then the remainder.
+        let left = value + size;
+
+        self.move_to(left);
+        self.push('+');
+        self.bf_loop(|cg| {
+            cg.push('-');
+            cg.push_n(radix.into(), '+');
+            cg.divide(value, size, false);
+
+            for cell in (digits..digits + count - 1).rev() {
+                cg.move_value(cell, &[cell + 1], 1);
+            }
+
+            let remainder = left;
+
+            if radix > 10 {
+                // The remainder is at least 10 unless counting it down
+                // (from one more) ten times hits zero.
+                let (counter, letter) = (remainder + size, remainder + size + 1);
+                let top = letter + 1;
+
+                cg.copy_value(remainder, counter, 1, top);
+                cg.move_to(counter);
+                cg.push('+');
+                cg.move_to(letter);
+                cg.push('+');
+
+                for _ in 0..10 {
+                    cg.move_to(counter);
+                    cg.push('-');
+                    cg.if_zero(
+                        counter,
+                        top,
+                        |cg| {
+                            cg.move_to(letter);
+                            cg.push('-');
+                        },
+                        |_| {},
+                    );
+                }
+
+                cg.move_to(counter);
+                cg.push_str("[-]");
+
+                cg.move_to(letter);
+                cg.bf_loop(|cg| {
+                    cg.push('-');
+                    cg.move_to(remainder);
+                    cg.push_n((b'a' - b'0' - 10).into(), '+');
+                    cg.move_to(letter);
+                });
+            }
+
+            cg.move_to(remainder);
+            cg.push_n(b'0'.into(), '+');
+            cg.move_value(remainder, &[digits], 1);
+
+            cg.copy_value(value, left, size, left + size);
+            cg.collapse(left, size);
+            cg.move_to(left);
+        });
+
+        for cell in digits..digits + count {
+            self.move_to(cell);
+            self.push_str("[.[-]]");
+        }
+    }
+
+    /// Prints the `length` characters at `location` up to the first
+    /// zero (if any), clearing them.
+    fn print_characters(&mut self, location: usize, length: usize, top: usize) {
+        let printing = top;
+        let scratch = top + 1;
+
+        self.move_to(printing);
+        self.push('+');
+
+        for cell in location..location + length {
+            self.if_zero(
+                cell,
+                scratch,
+                |cg| {
+                    cg.move_to(printing);
+                    cg.push_str("[-]");
+                },
+                |cg| {
+                    let flag = scratch + 1;
+
+                    cg.copy_value(printing, flag, 1, flag + 1);
+                    cg.move_to(flag);
+                    cg.bf_loop(|cg| {
+                        cg.push('-');
+                        cg.move_to(cell);
+                        cg.push('.');
+                        cg.move_to(flag);
+                    });
+                },
+            );
+
+            self.move_to(cell);
+            self.push_str("[-]");
+        }
+
+        self.move_to(printing);
+        self.push_str("[-]");
+    }
+}
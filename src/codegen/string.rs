@@ -0,0 +1,97 @@
+//! String literals, which can initialize `char` arrays or be printed
+//! by the `puts` and `print` intrinsics.
+
+use crate::{diagnostic::Diagnostic, treesitter_wrapper::*};
+
+use super::{Codegen, CodegenResult, ValueType, escape_value};
+
+/// Returns the bytes of a string literal (without the terminating
+/// zero), which are UTF-8 for anything beyond ASCII written out as is
+/// (an escape sequence is always the one byte it stands for).
+pub(super) fn string_value(node: &StringLiteral) -> CodegenResult<Vec<u8>> {
+    let mut bytes = vec![];
+
+    for child in &node.children {
+        match *child {
+            StringLiteralChildren::StringContent(ref content) => {
+                bytes.extend_from_slice(content.src.as_bytes());
+            }
+            StringLiteralChildren::EscapeSequence(ref es) => {
+                bytes.push(escape_value(es)?);
+            }
+        }
+    }
+
+    Ok(bytes)
+}
+
+impl Codegen {
+    /// Generates a string literal initializing a `char` array of type
+    /// `r#type` at `location`, which must be zeroed. As in C, the
+    /// terminating zero is left out if the array is just too short
+    /// to fit it.
+    pub(super) fn string_initializer(
+        &mut self,
+        node: &StringLiteral,
+        r#type: &ValueType,
+        location: usize,
+    ) -> CodegenResult {
+        let ValueType::Array(ref element, length) = *r#type else {
+            return Err(Diagnostic::new(
+                format!("cannot initialize a value of type `{type}` with a string literal"),
+                node.span,
+            ));
+        };
+
+        if !matches!(**element, ValueType::Char | ValueType::UnsignedChar) {
+            return Err(Diagnostic::new(
+                format!("cannot initialize an array of type `{type}` with a string literal"),
+                node.span,
+            ));
+        }
+
+        let bytes = string_value(node)?;
+
+        if bytes.len() > length {
+            return Err(Diagnostic::new(
+                format!("string literal is too long for `{type}`"),
+                node.span,
+            ));
+        }
+
+        let top = self.stack_pointer;
+
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.move_to(location + i);
+            self.push_n(byte.into(), '+');
+        }
+
+        self.move_to(top);
+
+        Ok(())
+    }
+
+    /// Prints `bytes` using the cell on top of the stack, which is
+    /// only ever changed by the difference between one byte and the
+    /// next (whichever way around is shorter), then cleared.
+    pub(super) fn print_bytes(&mut self, bytes: &[u8]) {
+        let mut current = 0u8;
+
+        for &byte in bytes {
+            let delta = byte.wrapping_sub(current);
+
+            if delta <= 128 {
+                self.push_n(delta.into(), '+');
+            } else {
+                self.push_n(delta.wrapping_neg().into(), '-');
+            }
+
+            self.push('.');
+            current = byte;
+        }
+
+        if current != 0 {
+            self.push_str("[-]");
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! `struct` types, whose fields are laid out one after the other
+//! (without any padding, since every cell is a byte anyway).
+
+use crate::{diagnostic::Diagnostic, treesitter_wrapper::*};
+
+use super::{
+    Codegen, CodegenResult, Environment, ValueType,
+    array::{Place, array_type, is_place},
+};
+
+/// The layout of a `struct` type.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct Struct {
+    /// The struct's tag (e.g., `point` in `struct point`), if any.
+    pub(super) name: Option<String>,
+    /// Name and type of each field, in order.
+    pub(super) fields: Vec<(String, ValueType)>,
+}
+
+impl Struct {
+    /// Returns the size (in bytes) of the struct, which is that of
+    /// all its fields put together.
+    pub(super) fn size(&self) -> usize {
+        self.fields.iter().map(|(_, r#type)| r#type.size()).sum()
+    }
+
+    /// Returns the location (relative to the struct's) and type of
+    /// each field, in order.
+    pub(super) fn members(&self) -> impl Iterator<Item = (usize, &ValueType)> {
+        self.fields.iter().scan(0, |offset, (_, r#type)| {
+            let location = *offset;
+            *offset += r#type.size();
+
+            Some((location, r#type))
+        })
+    }
+
+    /// Returns the location (relative to the struct's) and type of
+    /// the field called `name`, if there is one.
+    fn field(&self, name: &str) -> Option<(usize, &ValueType)> {
+        self.fields
+            .iter()
+            .zip(self.members())
+            .find(|((field, _), _)| field == name)
+            .map(|(_, member)| member)
+    }
+}
+
+impl ValueType {
+    /// Returns the `struct` type that a struct specifier occurring
+    /// within `env` refers to, which it defines if it has a body.
+    pub(super) fn from_struct_specifier(
+        spec: &StructSpecifier,
+        env: &Environment,
+    ) -> CodegenResult<Self> {
+        let name = spec.name.as_ref().map(|name| name.src.clone());
+
+        let Some(ref body) = spec.body else {
+            let name = spec
+                .name
+                .as_ref()
+                .expect("tree-sitter requires a name or a body");
+
+            return env.lookup_struct(&name.src).ok_or_else(|| {
+                Diagnostic::new(format!("unknown struct `{}`", name.src), name.span)
+            });
+        };
+
+        let mut fields: Vec<(String, ValueType)> = vec![];
+
+        for field in &body.children {
+            let (id, r#type) = interpret_field_declarator(
+                &field.declarator,
+                Self::from_type_specifier(&field.r#type, env)?,
+                env,
+            )?;
+
+            if r#type == Self::Void {
+                return Err(Diagnostic::new(
+                    format!("field `{}` cannot have type `void`", id.src),
+                    field.span,
+                ));
+            }
+
+            if fields.iter().any(|(other, _)| *other == id.src) {
+                return Err(Diagnostic::new(
+                    format!("duplicate field `{}`", id.src),
+                    id.span,
+                ));
+            }
+
+            fields.push((id.src.clone(), r#type));
+        }
+
+        let r#type = Self::Struct(Box::new(Struct { name, fields }));
+
+        if r#type.size() == 0 {
+            return Err(Diagnostic::new(
+                format!("`{type}` has no fields"),
+                spec.span,
+            ));
+        }
+
+        // like an array, it could be too big to index into
+        if r#type.size() > 1 << 16 {
+            return Err(Diagnostic::new(format!("`{type}` is too large"), spec.span));
+        }
+
+        Ok(r#type)
+    }
+
+    /// Returns the type of the field that a field expression
+    /// occurring within `env` refers to.
+    pub(super) fn from_field_expression(
+        node: &FieldExpression,
+        env: &Environment,
+    ) -> CodegenResult<Self> {
+        Ok(field_of(node, &Self::from_expression(&node.argument, env)?)?.1)
+    }
+}
+
+/// Returns the name and type that the declarator of a field
+/// (occurring within `env`) gives a type (`prior_type`), which it may
+/// make an array of.
+fn interpret_field_declarator<'d>(
+    declarator: &'d Declarator,
+    prior_type: ValueType,
+    env: &Environment,
+) -> CodegenResult<(&'d FieldIdentifier, ValueType)> {
+    match *declarator {
+        Declarator::FieldIdentifier(ref id) => Ok((id, prior_type)),
+        Declarator::ArrayDeclarator(ref ad) => {
+            interpret_field_declarator(&ad.declarator, array_type(ad, prior_type, None, env)?, env)
+        }
+        ref other => Err(Diagnostic::new(
+            "unsupported field declarator",
+            other.span(),
+        )),
+    }
+}
+
+/// Returns the location (relative to the struct's) and type of the
+/// field that `node` refers to within a value of type `r#type`.
+fn field_of(node: &FieldExpression, r#type: &ValueType) -> CodegenResult<(usize, ValueType)> {
+    // there are no pointers for it to go through
+    if let FieldOperator::Arrow(span) = *node.operator {
+        return Err(Diagnostic::new(
+            format!("cannot use `->` on a value of type `{type}`, which isn't a pointer"),
+            span,
+        ));
+    }
+
+    let field = match *r#type {
+        ValueType::Struct(ref layout) => layout.field(&node.field.src),
+        _ => None,
+    };
+
+    field
+        .map(|(offset, field)| (offset, field.clone()))
+        .ok_or_else(|| {
+            Diagnostic::new(
+                format!("no field `{}` on type `{type}`", node.field.src),
+                node.field.span,
+            )
+        })
+}
+
+impl Environment<'_> {
+    /// Returns the `struct` type with the tag `name`.
+    pub(super) fn lookup_struct(&self, name: &str) -> Option<ValueType> {
+        self.structs
+            .get(name)
+            .cloned()
+            .or_else(|| self.parent.and_then(|parent| parent.lookup_struct(name)))
+    }
+
+    /// Adds the `struct` that `spec` defines to the environment under
+    /// its tag, failing if the current scope already has one by that
+    /// name. Struct specifiers without both a tag and a body don't
+    /// define anything that lasts.
+    pub(super) fn add_struct(&mut self, spec: &StructSpecifier) -> CodegenResult {
+        let (Some(name), Some(_)) = (&spec.name, &spec.body) else {
+            return Ok(());
+        };
+
+        let r#type = ValueType::from_struct_specifier(spec, self)?;
+
+        if self.structs.insert(name.src.clone(), r#type).is_some() {
+            return Err(Diagnostic::new(
+                format!("redefinition of `struct {}`", name.src),
+                name.span,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Codegen {
+    /// Evaluates a field expression (i.e., `s.x`) and pushes the
+    /// field's value onto stack.
+    pub(super) fn field_expression(
+        &mut self,
+        node: &FieldExpression,
+        env: &Environment<'_>,
+    ) -> CodegenResult {
+        if is_place(&node.argument) {
+            let place = self.field_place(node, env)?;
+
+            let value = self.stack_pointer;
+            self.load(&place);
+            self.release(&place, value);
+
+            return Ok(());
+        }
+
+        // e.g., `f().x`, where the whole struct is pushed and all but
+        // the field is cleared again
+        let r#type = ValueType::from_expression(&node.argument, env)?;
+        let (offset, field) = field_of(node, &r#type)?;
+        let (location, size) = (self.stack_pointer, field.size());
+
+        self.expression(&node.argument, env)?;
+
+        for cell in
+            (location..location + offset).chain(location + offset + size..self.stack_pointer)
+        {
+            self.move_to(cell);
+            self.push_str("[-]");
+        }
+
+        if offset > 0 {
+            self.move_value(location + offset, &[location], size);
+        }
+        self.move_to(location + size);
+
+        Ok(())
+    }
+
+    /// Works out where the field that a field expression refers to
+    /// lives (see `place`).
+    pub(super) fn field_place(
+        &mut self,
+        node: &FieldExpression,
+        env: &Environment<'_>,
+    ) -> CodegenResult<Place> {
+        let r#type = ValueType::from_expression(&node.argument, env)?;
+        let (offset, field) = field_of(node, &r#type)?;
+
+        let mut place = self.place(&node.argument, env)?;
+        self.narrow(&mut place, offset, field);
+
+        Ok(place)
+    }
+}
@@ -0,0 +1,74 @@
+//! Compile errors and their presentation.
+
+use std::fmt::Write;
+
+use crate::treesitter_wrapper::Span;
+
+/// A problem with the C sourcecode, along with where it occurred.
+#[derive(Debug)]
+pub struct Diagnostic {
+    /// Short, lowercase description of the problem.
+    pub message: String,
+    /// The offending region of the source.
+    pub span: Span,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic pointing at `span`.
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span,
+        }
+    }
+
+    /// Renders the diagnostic in the style of rustc, quoting the
+    /// first line of the offending region from `src` (the contents
+    /// of the file at `path`) and underlining it.
+    ///
+    /// ```text
+    /// error: use of undeclared variable `x`
+    ///  --> input.c:3:5
+    ///   |
+    /// 3 |     x = 2;
+    ///   |     ^
+    /// ```
+    pub fn render(&self, src: &str, path: &str) -> String {
+        let Span { start, end, .. } = self.span;
+
+        let line = src.lines().nth(start.row).unwrap_or_default();
+        let line_number = (start.row + 1).to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        // Columns are in bytes, so walk the actual characters to keep
+        // the underline aligned (tabs included) with the quoted line.
+        let start_column = start.column.min(line.len());
+        let end_column = if end.row == start.row {
+            end.column.clamp(start_column, line.len())
+        } else {
+            line.len()
+        };
+
+        let padding: String = line[..start_column]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let underline = "^".repeat(line[start_column..end_column].chars().count().max(1));
+
+        let mut out = String::new();
+
+        writeln!(out, "error: {}", self.message).unwrap();
+        writeln!(
+            out,
+            "{gutter}--> {path}:{}:{}",
+            line_number,
+            line[..start_column].chars().count() + 1
+        )
+        .unwrap();
+        writeln!(out, "{gutter} |").unwrap();
+        writeln!(out, "{line_number} | {line}").unwrap();
+        write!(out, "{gutter} | {padding}{underline}").unwrap();
+
+        out
+    }
+}
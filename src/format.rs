@@ -1,17 +1,16 @@
-fn main() {
-    let src = "> +++ [-<+>] char a = 3;
-
-< [->+>+<<] >> [-<<+>>] push(a) < check [ [-] clear cond    {while(a) boilerplate}
-    < [->+>+<<] >> [-<<+>>] push(a) +> push(1)              {a = sub(a 1)}
-    <[-<->] sub() << [-] > [-<+>] mov(a)
-    < [->+>+<<] >> [-<<+>>] push(a) < check                 {more boilerplate}
-]";
-
+//! Pretty-printing for BF sourcecode.
+
+/// Lays out BF sourcecode (`src`) with every loop on its own
+/// lines and its body indented by one tab per nesting level.
+///
+/// Anything that isn't an instruction (comments, whitespace,
+/// etc.) is dropped.
+pub fn format(src: &str) -> String {
     let mut out = String::new();
 
     let mut depth = 0;
 
-    for (_index, c) in src.char_indices() {
+    for c in src.chars() {
         match c {
             '[' => {
                 out.push('\n');
@@ -40,5 +39,5 @@ fn main() {
         }
     }
 
-    println!("{out}");
+    out
 }
@@ -1,6 +1,12 @@
 //! Logic for executing a BF program.
 
-use std::io::{Read, stdin};
+use std::{
+    fmt,
+    io::{ErrorKind, Read, Write, stdin, stdout},
+};
+
+/// Number of cells on the tape.
+const TAPE_SIZE: usize = 30_000;
 
 /// The meaningful variants that every character
 /// in a BF program could encode.
@@ -17,8 +23,73 @@ pub enum Token {
     Debug,
 }
 
-/// Encodes BF sourcecode into a string of tokens.
-fn tokenize(chars: &mut impl Iterator<Item = char>) -> Vec<Token> {
+/// What `,` does to the current cell once there's no input left,
+/// which BF implementations don't agree on.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Eof {
+    /// Sets the cell to 0.
+    Zero,
+    /// Sets the cell to 255 (i.e., -1).
+    Max,
+    /// Leaves the cell as it was.
+    #[default]
+    Unchanged,
+}
+
+/// Reasons BF sourcecode can fail to tokenize.
+#[derive(Debug)]
+pub enum SyntaxError {
+    /// A character that is neither an instruction nor whitespace.
+    InvalidInstruction(char),
+    /// A `[` that is never closed.
+    UnclosedLoop,
+    /// A `]` without a matching `[`.
+    UnopenedLoop,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::InvalidInstruction(c) => write!(f, "invalid instruction: {c:?}"),
+            Self::UnclosedLoop => write!(f, "unclosed `[`"),
+            Self::UnopenedLoop => write!(f, "unmatched `]`"),
+        }
+    }
+}
+
+/// Reasons a BF program can fail to run.
+#[derive(Debug)]
+pub enum RunError {
+    /// The sourcecode couldn't be tokenized.
+    Syntax(SyntaxError),
+    /// The head moved off either end of the tape.
+    HeadOffTape,
+}
+
+impl From<SyntaxError> for RunError {
+    fn from(error: SyntaxError) -> Self {
+        Self::Syntax(error)
+    }
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Syntax(ref error) => error.fmt(f),
+            Self::HeadOffTape => write!(
+                f,
+                "the head moved off the tape (which has {TAPE_SIZE} cells)"
+            ),
+        }
+    }
+}
+
+/// Encodes BF sourcecode into a string of tokens. `depth`
+/// is the number of loops enclosing the current position.
+fn tokenize(
+    chars: &mut impl Iterator<Item = char>,
+    depth: usize,
+) -> Result<Vec<Token>, SyntaxError> {
     let mut tokens = vec![];
 
     while let Some(char) = chars.next() {
@@ -29,51 +100,95 @@ fn tokenize(chars: &mut impl Iterator<Item = char>) -> Vec<Token> {
             '-' => Token::DecVal,
             '.' => Token::PutChar,
             ',' => Token::GetChar,
-            '[' => Token::Loop(tokenize(chars)),
-            ']' => return tokens,
+            '[' => Token::Loop(tokenize(chars, depth + 1)?),
+            ']' if depth == 0 => return Err(SyntaxError::UnopenedLoop),
+            ']' => return Ok(tokens),
             '@' => Token::Debug,
             char if char.is_whitespace() => continue,
-            invalid => panic!("invalid instruction: {invalid}"),
+            invalid => return Err(SyntaxError::InvalidInstruction(invalid)),
         });
     }
 
-    tokens
+    if depth == 0 {
+        Ok(tokens)
+    } else {
+        Err(SyntaxError::UnclosedLoop)
+    }
 }
 
-/// Executes a slice of tokens, mutating `memory` and `ptr`.
-pub fn interpret(tokens: &[Token], memory: &mut [u8], ptr: &mut usize) {
+/// Executes a slice of tokens, mutating `memory` and `ptr` and
+/// writing each byte output as is to `out`. Reading past the end of
+/// input does whatever `eof` says. Fails if the head moves off `memory`.
+pub fn interpret(
+    tokens: &[Token],
+    memory: &mut [u8],
+    ptr: &mut usize,
+    eof: Eof,
+    out: &mut impl Write,
+) -> Result<(), RunError> {
     for token in tokens {
         match token {
-            Token::IncPtr => *ptr += 1,
-            Token::DecPtr => *ptr -= 1,
+            Token::IncPtr if *ptr + 1 < memory.len() => *ptr += 1,
+            Token::DecPtr if *ptr > 0 => *ptr -= 1,
+            Token::IncPtr | Token::DecPtr => return Err(RunError::HeadOffTape),
             Token::IncVal => memory[*ptr] = memory[*ptr].wrapping_add(1),
             Token::DecVal => memory[*ptr] = memory[*ptr].wrapping_sub(1),
-            Token::PutChar => print!("{}", char::from(memory[*ptr])),
+            Token::PutChar => out
+                .write_all(&[memory[*ptr]])
+                .expect("stdout should be writable"),
             Token::GetChar => {
+                // anything printed so far might be a prompt
+                out.flush().expect("stdout should be writable");
+
                 let mut buffer = [0; 1];
-                stdin().read_exact(&mut buffer).unwrap();
-                memory[*ptr] = buffer[0];
+                match stdin().read_exact(&mut buffer) {
+                    Ok(()) => memory[*ptr] = buffer[0],
+                    Err(err) if err.kind() == ErrorKind::UnexpectedEof => match eof {
+                        Eof::Zero => memory[*ptr] = 0,
+                        Eof::Max => memory[*ptr] = u8::MAX,
+                        Eof::Unchanged => {}
+                    },
+                    Err(err) => panic!("stdin should be readable: {err}"),
+                }
             }
             Token::Loop(tokens) => {
                 while memory[*ptr] != 0 {
-                    interpret(tokens, memory, ptr);
+                    interpret(tokens, memory, ptr, eof, out)?;
                 }
             }
-            Token::Debug => print(memory, *ptr),
+            Token::Debug => {
+                out.flush().expect("stdout should be writable");
+                print(memory, *ptr);
+            }
         }
     }
+
+    Ok(())
 }
 
-/// Executes BF sourcecode and prints the final memory state.
-pub fn run(src: &str) {
-    let tokens = tokenize(&mut src.chars());
+/// Executes BF sourcecode, printing the final memory state
+/// afterwards if `dump` is set.
+pub fn run(src: &str, dump: bool, eof: Eof) -> Result<(), RunError> {
+    let tokens = tokenize(&mut src.chars(), 0)?;
 
-    let mut memory = vec![0; 30_000].into_boxed_slice();
+    let mut memory = vec![0; TAPE_SIZE].into_boxed_slice();
     let mut ptr = 0;
 
-    interpret(&tokens, &mut memory[..], &mut ptr);
+    let mut out = stdout().lock();
+    let result = interpret(&tokens, &mut memory[..], &mut ptr, eof, &mut out);
+
+    // whatever got printed before things went wrong still shows up
+    out.flush().expect("stdout should be writable");
+    drop(out);
+    result?;
+
+    if dump {
+        print(&memory[..], ptr);
+    }
+
+    stdout().flush().expect("stdout should be writable");
 
-    print(&memory[..], ptr);
+    Ok(())
 }
 
 /// Pretty-prints the memory state and the head location.
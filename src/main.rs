@@ -1,15 +1,201 @@
+//! Command-line driver for cranium.
+
 mod codegen;
+mod diagnostic;
+mod format;
 mod interpreter;
 mod treesitter_wrapper;
 
-use crate::codegen::Codegen;
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    process::ExitCode,
+};
+
+use crate::{codegen::Codegen, interpreter::Eof};
+
+const USAGE: &str = "\
+usage: cranium <command> [options]
+
+commands:
+    compile <file.c> [-o <out.bf>]    compile C to BF (stdout by default)
+    run <file.c|file.bf> [--bf] [--dump] [--eof <0|255|unchanged>]
+                                      execute a C or BF program
+    fmt <file.bf> [-o <out.bf>]       pretty-print BF sourcecode
+    help                              show this message
+
+Passing `-` as a file reads from stdin (or writes to stdout). `run`
+treats files ending in `.bf` or `.b` (or any file with `--bf`) as BF
+and everything else as C. `--dump` prints the memory afterwards.
+`--eof` picks what reading past the end of input does to the cell:
+set it to 0 or 255, or leave it unchanged (the default).
+
+exit codes: 0 on success, 1 if the program is invalid or a file
+couldn't be accessed, 2 on bad usage.";
+
+/// A parsed invocation of cranium.
+enum Command {
+    Compile {
+        input: String,
+        output: Option<String>,
+    },
+    Run {
+        input: String,
+        bf: bool,
+        dump: bool,
+        eof: Eof,
+    },
+    Fmt {
+        input: String,
+        output: Option<String>,
+    },
+    Help,
+}
+
+impl Command {
+    /// Parses the command-line arguments (excluding the program
+    /// name), returning a usage complaint on failure.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let command = args.next().ok_or("missing command")?;
+
+        if matches!(command.as_str(), "help" | "-h" | "--help") {
+            return Ok(Self::Help);
+        }
+
+        let mut input = None;
+        let mut output = None;
+        let mut bf = false;
+        let mut dump = false;
+        let mut eof = Eof::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" if command != "run" => {
+                    output = Some(args.next().ok_or("`-o` requires a file")?);
+                }
+                "--bf" if command == "run" => bf = true,
+                "--dump" if command == "run" => dump = true,
+                "--eof" if command == "run" => {
+                    eof = match args.next().as_deref() {
+                        Some("0") => Eof::Zero,
+                        Some("255") => Eof::Max,
+                        Some("unchanged") => Eof::Unchanged,
+                        _ => return Err("`--eof` requires `0`, `255` or `unchanged`".to_string()),
+                    };
+                }
+                flag if flag.starts_with('-') && flag != "-" => {
+                    return Err(format!("unknown option `{flag}` for `{command}`"));
+                }
+                _ if input.is_some() => return Err(format!("unexpected argument `{arg}`")),
+                _ => input = Some(arg),
+            }
+        }
+
+        let input = input.ok_or_else(|| format!("`{command}` requires an input file"))?;
+
+        match command.as_str() {
+            "compile" => Ok(Self::Compile { input, output }),
+            "run" => Ok(Self::Run {
+                input,
+                bf,
+                dump,
+                eof,
+            }),
+            "fmt" => Ok(Self::Fmt { input, output }),
+            _ => Err(format!("unknown command `{command}`")),
+        }
+    }
+}
+
+/// Reads the file at `path`, or all of stdin if `path` is `-`.
+fn read_input(path: &str) -> io::Result<String> {
+    if path == "-" {
+        let mut buffer = String::new();
+        io::stdin().read_to_string(&mut buffer)?;
+
+        Ok(buffer)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+/// Writes `contents` to the file at `path`, or to stdout if
+/// there is no path (or it's `-`).
+fn write_output(path: Option<&str>, contents: &str) -> io::Result<()> {
+    match path {
+        Some(path) if path != "-" => fs::write(path, format!("{contents}\n")),
+        _ => writeln!(io::stdout(), "{contents}"),
+    }
+}
+
+/// Compiles C sourcecode (`src`, read from `path`) to BF. On
+/// failure, every diagnostic is printed to stderr.
+fn compile(src: &str, path: &str) -> Result<String, String> {
+    Codegen::new(src).generate().map_err(|diagnostics| {
+        let path = if path == "-" { "<stdin>" } else { path };
 
-fn main() {
-    let source = include_str!("../input.c");
+        for diagnostic in &diagnostics {
+            eprintln!("{}\n", diagnostic.render(src, path));
+        }
 
-    let output = Codegen::new(source).generate();
+        match diagnostics.len() {
+            1 => "aborting due to 1 previous error".to_string(),
+            n => format!("aborting due to {n} previous errors"),
+        }
+    })
+}
+
+/// Carries out `command`, returning a message describing
+/// the failure if it didn't succeed.
+fn execute(command: Command) -> Result<(), String> {
+    let read =
+        |path: &str| read_input(path).map_err(|err| format!("couldn't read `{path}`: {err}"));
+    let write = |path: Option<&str>, contents: &str| {
+        write_output(path, contents)
+            .map_err(|err| format!("couldn't write `{}`: {err}", path.unwrap_or("-")))
+    };
+
+    match command {
+        Command::Compile { input, output } => {
+            let bf = compile(&read(&input)?, &input)?;
+
+            write(output.as_deref(), &bf)
+        }
+        Command::Run {
+            input,
+            bf,
+            dump,
+            eof,
+        } => {
+            let src = read(&input)?;
+
+            let program = if bf || input.ends_with(".bf") || input.ends_with(".b") {
+                src
+            } else {
+                compile(&src, &input)?
+            };
+
+            interpreter::run(&program, dump, eof).map_err(|err| err.to_string())
+        }
+        Command::Fmt { input, output } => write(output.as_deref(), &format::format(&read(&input)?)),
+        Command::Help => write(None, USAGE),
+    }
+}
 
-    println!("codegen = \"{output}\"");
+fn main() -> ExitCode {
+    let command = match Command::parse(env::args().skip(1)) {
+        Ok(command) => command,
+        Err(complaint) => {
+            eprintln!("error: {complaint}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
 
-    interpreter::run(&output);
+    match execute(command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("error: {message}");
+            ExitCode::FAILURE
+        }
+    }
 }
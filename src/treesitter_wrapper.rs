@@ -1,6 +1,8 @@
 //! Usability wrapper for tree-sitter's horrendous API (particular
 //! to the C language implementation) for use in cranium.
 
+use std::fmt;
+
 use tree_sitter::Node as TSNode;
 
 /// Several `Node` variants grouped together.
@@ -16,6 +18,114 @@ trait NodeGroup {
     fn matches(node: &Node) -> bool;
 }
 
+/// A location within the source file, in rows and columns
+/// (both zero-based, with columns counted in bytes).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub row: usize,
+    pub column: usize,
+}
+
+/// The region of the source file that a node was parsed from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    /// Byte offset of the first character.
+    pub start_byte: usize,
+    /// Byte offset just past the last character.
+    pub end_byte: usize,
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    /// Takes the location of a treesitter node (`old`).
+    fn from_old(old: TSNode) -> Self {
+        let (start, end) = (old.start_position(), old.end_position());
+
+        Self {
+            start_byte: old.start_byte(),
+            end_byte: old.end_byte(),
+            start: Position {
+                row: start.row,
+                column: start.column,
+            },
+            end: Position {
+                row: end.row,
+                column: end.column,
+            },
+        }
+    }
+}
+
+/// A reason that part of the source couldn't be converted into nodes.
+#[derive(Debug, PartialEq)]
+pub enum ParseIssue {
+    /// Tree-sitter couldn't make sense of the code (an `ERROR` node).
+    Syntax,
+    /// Tree-sitter expected a token of this kind that isn't there
+    /// (a `MISSING` node).
+    Missing(&'static str),
+    /// A node lacks the field (or child) of this name.
+    MissingPart(&'static str),
+    /// A node has several of the field of this name where only one of
+    /// them fits (see `parts`).
+    Repeated(&'static str),
+    /// Valid C, but a kind of node cranium doesn't support.
+    Unsupported(&'static str),
+    /// A supported kind of node where cranium doesn't allow it.
+    Misplaced(&'static str),
+}
+
+impl fmt::Display for ParseIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Describes a tree-sitter node kind in plain words if it's
+        /// named (e.g., `switch_statement`), or quotes it otherwise.
+        fn describe(kind: &str) -> String {
+            if kind.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                kind.replace('_', " ")
+            } else {
+                format!("`{kind}`")
+            }
+        }
+
+        match *self {
+            Self::Syntax => write!(f, "syntax error"),
+            Self::Missing(kind) => write!(f, "missing {}", describe(kind)),
+            Self::MissingPart(name) => write!(f, "missing {name}"),
+            Self::Repeated(name) => write!(f, "multiple {name}s are not supported here"),
+            Self::Unsupported(kind) if kind.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                write!(f, "{}s are not supported", describe(kind))
+            }
+            Self::Unsupported(kind) => write!(f, "{} is not supported", describe(kind)),
+            Self::Misplaced(kind) => write!(f, "{} is not allowed here", describe(kind)),
+        }
+    }
+}
+
+/// Every problem that kept a file from being parsed, in the
+/// order they occur in the source.
+#[derive(Debug)]
+pub struct ParseError {
+    pub issues: Issues,
+}
+
+/// Problems found while converting, paired with where they occurred.
+type Issues = Vec<(ParseIssue, Span)>;
+
+/// Records every syntax error (`ERROR` and `MISSING` node)
+/// within `old` into `issues`.
+fn find_syntax_issues(old: TSNode, issues: &mut Issues) {
+    if old.is_error() {
+        issues.push((ParseIssue::Syntax, Span::from_old(old)));
+    } else if old.is_missing() {
+        issues.push((ParseIssue::Missing(old.kind()), Span::from_old(old)));
+    } else if old.has_error() {
+        for child in old.children(&mut old.walk()) {
+            find_syntax_issues(child, issues);
+        }
+    }
+}
+
 /// Takes a treesitter node (`old`) and the source
 /// file's code (`src`) and returns the slice associated
 /// with `old`.
@@ -42,24 +152,25 @@ fn old_get_src<'src>(old: TSNode, src: &'src str) -> &'src str {
 /// entry with a prepended token (or the absence of one).
 /// 
 /// 1. **Top-level nodes** (no prefix): Regular nodes which have
-/// associated data that just knowing the variant isn't enough
-/// information for (e.g., `Identifier`s, because you still need
-/// the text they refer to).
+///    associated data that just knowing the variant isn't enough
+///    information for (e.g., `Identifier`s, because you still need
+///    the text they refer to).
 /// 
 /// 2. **Data-less (Unit) nodes** (`~` prefix): Just like (1) but
-/// there is no further information needed to interpret them than
-/// the variant (e.g., `Plus`s). The associated `Node` variant must
-/// also be data-less.
+///    there is no further information needed to interpret them than
+///    the variant (e.g., `Plus`s). The associated `Node` variant must
+///    also be data-less. Both only carry the node's `Span`.
 /// 
 /// 3. **Other node groups** (`*` prefix): Other node groups defined
-/// in this macro invocation (e.g., `Declarator`s).
+///    in this macro invocation (e.g., `Declarator`s).
 /// 
 /// Variants must be declared in this order (just a limitation of
 /// `macro_rules!` macros).
 /// 
 /// This gets converted into a `pub enum` with variants named exactly
 /// as they're provided, and the data-ful ones each come with a `Box`
-/// to the type of the same name as the variant. For this reason,
+/// to the type of the same name as the variant (the data-less ones come
+/// with their `Span`). For this reason,
 /// **every variant name must be identical to its associated type**.
 /// This was chosen for simplicity as well as unambiguity, although
 /// perhaps at the expense of consiceness.
@@ -82,7 +193,7 @@ fn old_get_src<'src>(old: TSNode, src: &'src str) -> &'src str {
 /// enum GroupName {
 ///     MemberNode1(Box<MemberNode1>),
 ///     MemberNode2(Box<MemberNode2>),
-///     NoDataMemberNode, // Always matches to `Node::NoDataMemberNode`
+///     NoDataMemberNode(Span), // Always matches to `Node::NoDataMemberNode`
 ///     OtherGroup(Box<OtherGroup>),
 /// }
 /// 
@@ -91,7 +202,7 @@ fn old_get_src<'src>(old: TSNode, src: &'src str) -> &'src str {
 ///         match node {
 ///             Node::MemberNode1(n) => Some(Self::MemberNode1(n)),
 ///             Node::MemberNode2(n) => Some(Self::MemberNode1(n)),
-///             Node::NoDataMemberNode => Some(Self::NoDataMemberNode),
+///             Node::NoDataMemberNode(span) => Some(Self::NoDataMemberNode(span)),
 ///             n if OtherGroup::matches(&n) => Some(Self::from_node(n).unwrap()),
 ///             _ => None,
 ///         }
@@ -101,13 +212,24 @@ fn old_get_src<'src>(old: TSNode, src: &'src str) -> &'src str {
 ///         match node {
 ///             Node::MemberNode1(_) => true,
 ///             Node::MemberNode2(_) => true,
-///             Node::NoDataMemberNode => true,
+///             Node::NoDataMemberNode(_) => true,
 ///             n if OtherGroup::matches(n) => true,
 ///             _ => false,
 ///         }
 ///     }
 /// }
 /// 
+/// impl GroupName {
+///     pub fn span(&self) -> Span {
+///         match *self {
+///             Self::MemberNode1(ref n) => n.span,
+///             Self::MemberNode2(ref n) => n.span,
+///             Self::NoDataMemberNode(span) => span,
+///             Self::OtherGroup(ref g) => g.span(),
+///         }
+///     }
+/// }
+/// 
 /// /// ...
 /// ```
 macro_rules! declare_node_groups {
@@ -119,9 +241,12 @@ macro_rules! declare_node_groups {
         }),+ $(,)?
     ) => {
         $(
+            // Variant names must match their types, so shared
+            // suffixes (e.g., `...Statement`) are unavoidable.
+            #[allow(clippy::enum_variant_names)]
             pub enum $group_name {
                 $($member(Box<$member>),)*
-                $($unit_member,)*
+                $($unit_member(#[allow(dead_code)] Span),)*
                 $($member_group(Box<$member_group>),)*
             }
 
@@ -129,7 +254,7 @@ macro_rules! declare_node_groups {
                 fn from_node(node: Node) -> Option<Self> {
                     match node {
                         $(Node::$member(n) => Some(Self::$member(n)),)*
-                        $(Node::$unit_member => Some(Self::$unit_member),)*
+                        $(Node::$unit_member(span) => Some(Self::$unit_member(span)),)*
                         $(n if $member_group::matches(&n) => Some(
                             Self::$member_group(
                                 Box::new($member_group::from_node(n).unwrap())
@@ -143,12 +268,24 @@ macro_rules! declare_node_groups {
                 fn matches(node: &Node) -> bool {
                     match node {
                         $(Node::$member(_) => true,)*
-                        $(Node::$unit_member => true,)*
+                        $(Node::$unit_member(_) => true,)*
                         $(x if $member_group::matches(x) => true,)*
                         _ => false,
                     }
                 }
             }
+
+            impl $group_name {
+                /// Returns the location of whichever node this is.
+                #[allow(dead_code)]
+                pub fn span(&self) -> Span {
+                    match *self {
+                        $(Self::$member(ref n) => n.span,)*
+                        $(Self::$unit_member(span) => span,)*
+                        $(Self::$member_group(ref g) => g.span(),)*
+                    }
+                }
+            }
         )+
     };
 }
@@ -185,63 +322,74 @@ macro_rules! field_stringify {
 /// data "fields" that you may provide are (1) whether - and by what
 /// moniker - to associate the node with its source code, (2) the
 /// actual fields that the node will have, and (3) the child(ren)
-/// that the node will have.
+/// that the node will have. Regardless of those, every node has a
+/// `span` field holding its location within the source file.
 /// 
 /// 1. **Sourcecode endpoint** (`@` prefix): If defined, specifies
-/// what identifier the sourcecode associated with the node should
-/// be. This is generally `src` but the option is available for
-/// something else.
+///    what identifier the sourcecode associated with the node should
+///    be. This is generally `src` but the option is available for
+///    something else.
 /// 
 /// 2. **Fields**: If defined, constitute the fields - required or
-/// optional - that the node possesses. They are specified by `field:`
-/// followed by a curly-brace-enclosed comma-separated list of fields,
-/// which come in 4 separate forms:
+///    optional - that the node possesses. They are specified by `field:`
+///    followed by a curly-brace-enclosed comma-separated list of fields,
+///    which come in 4 separate forms:
 /// 
-///     a. **Single-variant required field** (no prefix): Field
-///     where there is exactly one node variant that it may be (e.g.,
-///     `FunctionDefinition`'s `body` field must be a `CompoundStatement`
-///     and nothing else).
+///    a. **Single-variant required field** (no prefix): Field
+///    where there is exactly one node variant that it may be (e.g.,
+///    `FunctionDefinition`'s `body` field must be a `CompoundStatement`
+///    and nothing else).
 /// 
-///     b. **Multi-variant required field** (`*` prefix): Field
-///     where there are several variants that would satisfy the field,
-///     which must have a corresponding node group (see `declare_node_groups!`)
-///     (e.g., `Declaration`'s `declarator` field may be any node variant
-///     contained in `Declarator`).
+///    b. **Multi-variant required field** (`*` prefix): Field
+///    where there are several variants that would satisfy the field,
+///    which must have a corresponding node group (see `declare_node_groups!`)
+///    (e.g., `Declaration`'s `declarator` field may be any node variant
+///    contained in `Declarator`).
 /// 
-///     c. **Single-variant optional field** (`?` prefix): Just like
-///     its required counterpart, but it may also not be fulfilled at all.
+///    c. **Single-variant optional field** (`?` prefix): Just like
+///    its required counterpart, but it may also not be fulfilled at all.
 /// 
-///     c. **Multi-variant optional field** (`?*` prefix): Just like
-///     its required counterpart, but it may also not be fulfilled at all.
+///    d. **Multi-variant optional field** (`?*` prefix): Just like
+///    its required counterpart, but it may also not be fulfilled at all.
+/// 
+///    e. **Lowered multi-variant field** (`[*]` prefix): Just like an
+///    optional one, but holding every node the field lowers to (see
+///    below), so there may be any number of them.
 /// 
 /// 3. **Children**: If defined, describe potential children of the
-/// node and the variants they may be of. Unlike fields, children do
-/// not associate with any name in relation to the parent node. They
-/// may also exist in arbitrary numbers in some cases, which is very
-/// useful. The child(ren) of a node may be set in one of four
-/// configurations(future iterations may have more):
+///    node and the variants they may be of. Unlike fields, children do
+///    not associate with any name in relation to the parent node. They
+///    may also exist in arbitrary numbers in some cases, which is very
+///    useful. The child(ren) of a node may be set in one of four
+///    configurations(future iterations may have more):
+/// 
+///    a. **Single-variant multi-count children** (`children:` prefix):
+///    Children who have only a single variant that they may be and there
+///    may also be several instances of them.
 /// 
-///     a. **Single-variant multi-count children** (`children:` prefix):
-///     Children who have only a single variant that they may be and there
-///     may also be several instances of them.
+///    b. **Multi-variant multi-count children** (`* children:` prefix):
+///    Just like their single-variant counterparts but they may be of the
+///    form of any member of a specified group (see `declare_node_groups!`).
 /// 
-///     b. **Multi-variant multi-count children** (`* children:` prefix):
-///     Just like their single-variant counterparts but they may be of the
-///     form of any member of a specified group (see `declare_node_groups!`).
+///    c. **Single-variant single-count children** (`child:` prefix):
+///    Just like their multi-count counterparts but there may only exist
+///    one child of the node.
 /// 
-///     c. **Single-variant single-count children** (`child:` prefix):
-///     Just like their multi-count counterparts but there may only exist
-///     one child of the node.
+///    d. **Multi-variant single-count children** (`* child:` prefix):
+///    Just like their single-variant counterparts but the node may be in
+///    the form of any member of a specified group (see `declare_node_groups!`).
 /// 
-///     d. **Multi-variant single-count children** (`* child:` prefix):
-///     Just like their single-variant counterparts but the node may be in
-///     the form of any member of a specified group (see `declare_node_groups!`).
+/// A node with a field that's repeated (e.g., `int a, b;`, which has two
+/// `declarator`s) gets lowered into several nodes, one for each of the
+/// repeated field (e.g., `int a; int b;`), wherever it's a child (and
+/// for a lowered field). Anywhere else, only one of them would fit.
 /// 
 /// After all those nodes have been specified, there exists a space for
 /// data-less (unit) node variants. They carry with them no semantic
 /// information other than their variant and how tree-sitter expressed
-/// them. They are specified like all the nodes prior, but before them
-/// comes a `~` and there are no curly braces (e.g., `~ Variant ("variant"),`).
+/// them (besides their `Span`). They are specified like all the nodes
+/// prior, but before them comes a `~` and there are no curly braces
+/// (e.g., `~ Variant ("variant"),`).
 /// 
 /// The top-level type (`Node` in cranium) is an enum over all the supplied
 /// variants, and also implements the `from_old` function, which takes
@@ -277,6 +425,7 @@ macro_rules! declare_nodes {
                         $(* $mv_field_name:ident: $mv_field_type:ident,)*
                         $(? $sv_opt_field_name:ident: $sv_opt_field_type:ident,)*
                         $(?* $mv_opt_field_name:ident: $mv_opt_field_type:ident,)*
+                        $([*] $lw_field_name:ident: $lw_field_type:ident,)*
                     },
                 )?
                 $(children: $sv_children_type:ident,)?
@@ -291,12 +440,16 @@ macro_rules! declare_nodes {
     ) => {
         $(
             pub struct $variant_name {
-                $(pub $src_name: String)?
+                $(pub $src_name: String,)?
+                // not every node's location gets used (yet)
+                #[allow(dead_code)]
+                pub span: Span,
                 $(
                     $(pub $sv_field_name: Box<$sv_field_type>,)*
                     $(pub $mv_field_name: Box<$mv_field_type>,)*
                     $(pub $sv_opt_field_name: Option<Box<$sv_opt_field_type>>,)*
                     $(pub $mv_opt_field_name: Option<Box<$mv_opt_field_type>>,)*
+                    $(pub $lw_field_name: Vec<$lw_field_type>,)*
                 )?
                 $(pub children: Vec<$sv_children_type>,)?
                 $(pub children: Vec<$mv_children_type>,)?
@@ -308,114 +461,268 @@ macro_rules! declare_nodes {
         enum $top_level_type {
             // #[allow(dead_code)]
             $($variant_name(Box<$variant_name>),)*
-            $($unit_variant_name,)*
+            $($unit_variant_name(Span),)*
         }
 
         impl $top_level_type {
-            fn from_old(old: TSNode, src: &str) -> Self {
+            /// Converts a treesitter node (`old`) into a `Node`, given the
+            /// source file's code (`src`). Returns `None` if `old` or anything
+            /// within it couldn't be converted, having pushed the reasons why
+            /// onto `issues`. Syntax errors are left to `find_syntax_issues`.
+            fn from_old(old: TSNode, src: &str, issues: &mut Issues) -> Option<Self> {
+                Self::from_old_part(old, 0, src, issues)
+            }
+
+            /// Converts `old` like `from_old`, taking the `part`th of any
+            /// field it repeats (see `parts`).
+            fn from_old_part(
+                old: TSNode,
+                part: usize,
+                src: &str,
+                issues: &mut Issues,
+            ) -> Option<Self> {
+                if old.is_error() || old.is_missing() {
+                    return None;
+                }
+
                 match old.kind() {
                     $($old_name => {
-                        Self::$variant_name(Box::new($variant_name {
+                        // Everything is converted before bailing so that the
+                        // issues within each part get recorded.
+                        // fields
+                        $(
+                        $(let $sv_field_name = Self::field(old, field_stringify!($sv_field_name), part, issues)
+                            .and_then(|field| Self::from_old_as(field, src, issues, |node| match node {
+                                Self::$sv_field_type(x) => Some(x),
+                                _ => None,
+                            }));
+                        )*
+                        $(let $mv_field_name = Self::field(old, field_stringify!($mv_field_name), part, issues)
+                            .and_then(|field| Self::from_old_as(field, src, issues, $mv_field_type::from_node));
+                        )*
+                        $(let $sv_opt_field_name = old
+                            .child_by_field_name(field_stringify!($sv_opt_field_name))
+                            .map(|field| Self::from_old_as(field, src, issues, |node| match node {
+                                Self::$sv_opt_field_type(x) => Some(x),
+                                _ => None,
+                            }));
+                        )*
+                        $(let $mv_opt_field_name = old
+                            .child_by_field_name(field_stringify!($mv_opt_field_name))
+                            .map(|field| Self::from_old_as(field, src, issues, $mv_opt_field_type::from_node));
+                        )*
+                        $(let $lw_field_name: Vec<Option<$lw_field_type>> = old
+                            .child_by_field_name(field_stringify!($lw_field_name))
+                            .map_or(vec![], |field| Self::lowered(field, src, issues, $lw_field_type::from_node));
+                        )*
+                        )?
+                        // children
+                        $(
+                        let children: Vec<Option<$sv_children_type>> = Self::children(old)
+                            .flat_map(|child| Self::lowered(child, src, issues, |node| match node {
+                                Self::$sv_children_type(x) => Some(*x),
+                                _ => None,
+                            }))
+                            .collect();
+                        )?
+                        $(
+                        let children: Vec<Option<$mv_children_type>> = Self::children(old)
+                            .flat_map(|child| Self::lowered(child, src, issues, $mv_children_type::from_node))
+                            .collect();
+                        )?
+                        $(
+                        let child = Self::child(old, issues)
+                            .and_then(|child| Self::from_old_as(child, src, issues, |node| match node {
+                                Self::$sv_child_type(x) => Some(x),
+                                _ => None,
+                            }));
+                        )?
+                        $(
+                        let child = Self::child(old, issues)
+                            .and_then(|child| Self::from_old_as(child, src, issues, $mv_child_type::from_node));
+                        )?
+
+                        Some(Self::$variant_name(Box::new($variant_name {
                             $($src_name: old_get_src(old, src).to_string(),)?
-                            // fields
-                            $(
-                            $($sv_field_name: Box::new(
-                                match Self::from_old(
-                                    old.child_by_field_name(field_stringify!($sv_field_name))
-                                        .expect("Field should be defined"),
-                                    src,
-                                ) {
-                                    Self::$sv_field_type(x) => *x,
-                                    _ => unimplemented!(),
-                                }
-                            ),)*
-                            $($mv_field_name: Box::new(
-                                match $mv_field_type::from_node(Self::from_old(
-                                    old.child_by_field_name(field_stringify!($mv_field_name))
-                                        .expect(concat!("Field should be defined")),
-                                    src,
-                                )) {
-                                    Some(x) => x,
-                                    None => panic!("Field node not member of set"),
-                                }
-                            ),)*
-                            $($sv_opt_field_name: old
-                                .child_by_field_name(field_stringify!($sv_opt_field_name))
-                                .and_then(|old_node| match Self::from_old(old_node, src) {
-                                    Self::$sv_opt_field_type(x) => Some(x),
-                                    _ => unimplemented!(),
-                                }),
-                            )*
-                            $($mv_opt_field_name: old
-                                .child_by_field_name(field_stringify!($mv_opt_field_name))
-                                .and_then(|old_node| match $mv_opt_field_type::from_node(
-                                    Self::from_old(old_node, src)
-                                ) {
-                                    Some(x) => Some(Box::new(x)),
-                                    None => panic!("Field node not member of set"),
-                                }),
-                            )*
-                            )?
-                            // children
-                            $(
-                            children: old
-                                .named_children(&mut old.walk())
-                                .map(|n| match Self::from_old(n, src) {
-                                    Self::$sv_children_type(x) => *x,
-                                    _ => unimplemented!(),
-                                }).collect(),
-                            )?
-                            $(
-                            children: old
-                                .named_children(&mut old.walk())
-                                .map(|n| match $mv_children_type::from_node(
-                                    Self::from_old(n, src)
-                                ) {
-                                    Some(x) => x,
-                                    None => panic!("Field node not member of set"),
-                                }).collect(),
-                            )?
-                            $(
-                            child: match Self::from_old(
-                                old.named_children(&mut old.walk())
-                                    .nth(0)
-                                    .expect("Node should have a child node"),
-                                src,
-                            ) {
-                                Self::$sv_child_type(x) => x,
-                                _ => unimplemented!(),
-                            },
-                            )?
+                            span: Span::from_old(old),
                             $(
-                            child: match $mv_child_type::from_node(
-                                Self::from_old(old
-                                    .named_children(&mut old.walk())
-                                        .nth(0)
-                                        .expect("Node should have a child node"),
-                                    src,
-                                )
-                            ) {
-                                Some(x) => Box::new(x),
-                                None => panic!("Field node not member of set"),
-                            },
+                            $($sv_field_name: $sv_field_name?,)*
+                            $($mv_field_name: Box::new($mv_field_name?),)*
+                            $($sv_opt_field_name: match $sv_opt_field_name {
+                                Some(field) => Some(field?),
+                                None => None,
+                            },)*
+                            $($mv_opt_field_name: match $mv_opt_field_name {
+                                Some(field) => Some(Box::new(field?)),
+                                None => None,
+                            },)*
+                            $($lw_field_name: $lw_field_name.into_iter().collect::<Option<_>>()?,)*
                             )?
-                        }))
+                            $(children: {
+                                let _: &[Option<$sv_children_type>] = &children;
+                                children.into_iter().collect::<Option<_>>()?
+                            },)?
+                            $(children: {
+                                let _: &[Option<$mv_children_type>] = &children;
+                                children.into_iter().collect::<Option<_>>()?
+                            },)?
+                            $(child: {
+                                let _: &Option<Box<$sv_child_type>> = &child;
+                                child?
+                            },)?
+                            $(child: Box::new({
+                                let _: &Option<$mv_child_type> = &child;
+                                child?
+                            }),)?
+                        })))
                     },)*
-                    $($unit_old_name => Self::$unit_variant_name,)*
-                    kind => unimplemented!("{}", kind),
+                    $($unit_old_name => Some(Self::$unit_variant_name(Span::from_old(old))),)*
+                    kind => {
+                        issues.push((ParseIssue::Unsupported(kind), Span::from_old(old)));
+
+                        None
+                    }
                 }
             }
+
+            /// Converts `old` like `from_old`, then narrows the result with
+            /// `narrow` (e.g., into a node group), recording an issue if `old`
+            /// turns out to be the wrong kind of node or would be lowered
+            /// into several (see `parts`).
+            fn from_old_as<T>(
+                old: TSNode,
+                src: &str,
+                issues: &mut Issues,
+                narrow: impl FnOnce(Self) -> Option<T>,
+            ) -> Option<T> {
+                if let Some((name, _)) = Self::repeated_field(old) {
+                    let extra = old.children_by_field_name(name, &mut old.walk()).nth(1);
+                    issues.push((ParseIssue::Repeated(name), Span::from_old(extra.unwrap_or(old))));
+                }
+
+                Self::from_old_part_as(old, 0, src, issues, narrow)
+            }
+
+            /// Converts `old` like `from_old_part`, then narrows the result
+            /// like `from_old_as`.
+            fn from_old_part_as<T>(
+                old: TSNode,
+                part: usize,
+                src: &str,
+                issues: &mut Issues,
+                narrow: impl FnOnce(Self) -> Option<T>,
+            ) -> Option<T> {
+                let narrowed = narrow(Self::from_old_part(old, part, src, issues)?);
+
+                if narrowed.is_none() {
+                    issues.push((ParseIssue::Misplaced(old.kind()), Span::from_old(old)));
+                }
+
+                narrowed
+            }
+
+            /// Converts `old` into every node it lowers to (see `parts`),
+            /// narrowing each like `from_old_as`.
+            fn lowered<T>(
+                old: TSNode,
+                src: &str,
+                issues: &mut Issues,
+                narrow: impl Fn(Self) -> Option<T>,
+            ) -> Vec<Option<T>> {
+                (0..Self::parts(old))
+                    .map(|part| Self::from_old_part_as(old, part, src, issues, &narrow))
+                    .collect()
+            }
+
+            /// Returns how many nodes `old` gets lowered into: one for each
+            /// of the field it repeats (e.g., the two `declarator`s of
+            /// `int a, b;`), if any.
+            fn parts(old: TSNode) -> usize {
+                Self::repeated_field(old).map_or(1, |(_, count)| count)
+            }
+
+            /// Returns the name of the field of `old` that's repeated the
+            /// most, and how many times, if there's one repeated at all.
+            fn repeated_field(old: TSNode) -> Option<(&'static str, usize)> {
+                let mut counts: Vec<(&'static str, usize)> = vec![];
+
+                for name in (0..old.child_count())
+                    .filter_map(|i| u32::try_from(i).ok().and_then(|i| old.field_name_for_child(i)))
+                {
+                    match counts.iter_mut().find(|(counted, _)| *counted == name) {
+                        Some((_, count)) => *count += 1,
+                        None => counts.push((name, 1)),
+                    }
+                }
+
+                counts
+                    .into_iter()
+                    .filter(|&(_, count)| count > 1)
+                    .max_by_key(|&(_, count)| count)
+            }
+
+            /// Returns the field of `old` called `name` (or its `part`th, if
+            /// it's repeated), recording an issue if it's absent (unless a
+            /// syntax error is to blame).
+            fn field<'tree>(
+                old: TSNode<'tree>,
+                name: &'static str,
+                part: usize,
+                issues: &mut Issues,
+            ) -> Option<TSNode<'tree>> {
+                let field = old
+                    .children_by_field_name(name, &mut old.walk())
+                    .nth(part)
+                    .or_else(|| old.child_by_field_name(name));
+
+                if field.is_none() && !old.has_error() {
+                    issues.push((ParseIssue::MissingPart(name), Span::from_old(old)));
+                }
+
+                field
+            }
+
+            /// Returns the meaningful (i.e., named and not a comment)
+            /// children of `old`, leaving out its fields.
+            fn children<'tree>(old: TSNode<'tree>) -> impl Iterator<Item = TSNode<'tree>> {
+                (0..old.child_count())
+                    .filter(move |&i| {
+                        u32::try_from(i).is_ok_and(|i| old.field_name_for_child(i).is_none())
+                    })
+                    .filter_map(move |i| old.child(i))
+                    .filter(|child| child.is_named() && !child.is_extra())
+            }
+
+            /// Returns the first meaningful child of `old`, recording an
+            /// issue if it has none (unless a syntax error is to blame).
+            fn child<'tree>(old: TSNode<'tree>, issues: &mut Issues) -> Option<TSNode<'tree>> {
+                let child = Self::children(old).next();
+
+                if child.is_none() && !old.has_error() {
+                    issues.push((ParseIssue::MissingPart("child"), Span::from_old(old)));
+                }
+
+                child
+            }
         }
     };
 }
 
 declare_node_groups! {
     Statement {
+        CaseStatement,
         CompoundStatement,
+        DoStatement,
         ExpressionStatement,
         ForStatement,
+        GotoStatement,
         IfStatement,
+        LabeledStatement,
+        ReturnStatement,
+        SwitchStatement,
         WhileStatement,
+        ~ BreakStatement,
+        ~ ContinueStatement,
     },
     Expression {
         AssignmentExpression,
@@ -425,53 +732,115 @@ declare_node_groups! {
         Identifier,
         NumberLiteral,
         UpdateExpression,
+        UnaryExpression,
         ParenthesizedExpression,
+        SubscriptExpression,
+        FieldExpression,
+        StringLiteral,
+        ConditionalExpression,
+        CommaExpression,
         ~ False,
         ~ True,
     },
     TypeSpecifier {
         PrimitiveType,
+        SizedTypeSpecifier,
         TypeIdentifier,
+        StructSpecifier,
+        EnumSpecifier,
     },
     Declarator {
         Identifier,
+        TypeIdentifier,
         InitDeclarator,
         FunctionDeclarator,
+        ArrayDeclarator,
+        FieldIdentifier,
     },
     BinaryOperator {
         ~ EqualsCheck,
         ~ NotEqualsCheck,
+        ~ LessThan,
+        ~ GreaterThan,
+        ~ LessThanOrEqual,
+        ~ GreaterThanOrEqual,
+        ~ LogicalAnd,
+        ~ LogicalOr,
         ~ Plus,
         ~ Minus,
+        ~ Times,
+        ~ Divide,
+        ~ Modulo,
+        ~ BitwiseAnd,
+        ~ BitwiseOr,
+        ~ BitwiseXor,
+        ~ ShiftLeft,
+        ~ ShiftRight,
     },
     BlockChild {
         Declaration,
         TypeDefinition,
+        StructSpecifier,
+        EnumSpecifier,
         * Statement,
     },
     ForLoopInitializer {
         Declaration,
         * Expression,
     },
+    DeclarationSpecifier {
+        StorageClassSpecifier,
+        TypeQualifier,
+    },
     CharLiteralChildren {
         Character,
         EscapeSequence,
     },
+    StringLiteralChildren {
+        StringContent,
+        EscapeSequence,
+    },
     AssignmentOperator {
         ~ AssignEquals,
         ~ PlusEquals,
         ~ MinusEquals,
+        ~ TimesEquals,
+        ~ DivideEquals,
+        ~ ModuloEquals,
+        ~ AndEquals,
+        ~ OrEquals,
+        ~ XorEquals,
+        ~ ShiftLeftEquals,
+        ~ ShiftRightEquals,
     },
     UpdateOperator {
         ~ PlusPlus,
         ~ MinusMinus,
     },
+    UnaryOperator {
+        ~ Plus,
+        ~ Minus,
+        ~ Not,
+        ~ BitwiseNot,
+    },
     TypeDeclarator {
         TypeIdentifier,
+        ArrayDeclarator,
+    },
+    Initializer {
+        InitializerList,
+        * Expression,
     },
     TUChildren {
+        Declaration,
         FunctionDefinition,
         TypeDefinition,
+        StructSpecifier,
+        EnumSpecifier,
+    },
+    FieldOperator {
+        ~ Dot,
+        ~ Arrow,
     },
 }
 
@@ -495,13 +864,21 @@ declare_nodes! {
             * declarator: Declarator,
             * r#type: TypeSpecifier,
         },
+        * children: DeclarationSpecifier,
     },
+    StorageClassSpecifier ("storage_class_specifier") {
+        @src,
+    },
+    TypeQualifier ("type_qualifier") {},
     Identifier ("identifier") {
         @src,
     },
     PrimitiveType ("primitive_type") {
         @src,
     },
+    SizedTypeSpecifier ("sized_type_specifier") {
+        @src,
+    },
     CharLiteral ("char_literal") {
         * children: CharLiteralChildren,
     },
@@ -511,15 +888,22 @@ declare_nodes! {
     Character ("character") {
         @src,
     },
+    StringLiteral ("string_literal") {
+        * children: StringLiteralChildren,
+    },
+    StringContent ("string_content") {
+        @src,
+    },
     EscapeSequence ("escape_sequence") {
         @src,
     },
+    // an empty statement (just `;`) has no children
     ExpressionStatement ("expression_statement") {
-        * child: Expression,
+        * children: Expression,
     },
     AssignmentExpression ("assignment_expression") {
         fields: {
-            left: Identifier,
+            * left: Expression,
             * operator: AssignmentOperator,
             * right: Expression,
         },
@@ -540,6 +924,32 @@ declare_nodes! {
     ParenthesizedExpression ("parenthesized_expression") {
         * child: Expression,
     },
+    SubscriptExpression ("subscript_expression") {
+        fields: {
+            * argument: Expression,
+            * index: Expression,
+        },
+    },
+    FieldExpression ("field_expression") {
+        fields: {
+            field: FieldIdentifier,
+            * argument: Expression,
+            * operator: FieldOperator,
+        },
+    },
+    ConditionalExpression ("conditional_expression") {
+        fields: {
+            * condition: Expression,
+            * consequence: Expression,
+            * alternative: Expression,
+        },
+    },
+    CommaExpression ("comma_expression") {
+        fields: {
+            * left: Expression,
+            * right: Expression,
+        },
+    },
     ArgumentList ("argument_list") {
         * children: Expression,
     },
@@ -548,7 +958,8 @@ declare_nodes! {
             * body: Statement,
             ?* condition: Expression,
             ?* update: Expression,
-            ?* initializer: ForLoopInitializer,
+            // one for each declarator of a declaration
+            [*] initializer: ForLoopInitializer,
         },
     },
     IfStatement ("if_statement") {
@@ -558,18 +969,62 @@ declare_nodes! {
             ? alternative: ElseClause,
         },
     },
+    ReturnStatement ("return_statement") {
+        * children: Expression,
+    },
     WhileStatement ("while_statement") {
         fields: {
             condition: ParenthesizedExpression,
             * body: Statement,
         },
     },
+    DoStatement ("do_statement") {
+        fields: {
+            condition: ParenthesizedExpression,
+            * body: Statement,
+        },
+    },
+    SwitchStatement ("switch_statement") {
+        fields: {
+            body: CompoundStatement,
+            condition: ParenthesizedExpression,
+        },
+    },
+    CaseStatement ("case_statement") {
+        fields: {
+            ?* value: Expression,
+        },
+        * children: BlockChild,
+    },
+    LabeledStatement ("labeled_statement") {
+        fields: {
+            label: StatementIdentifier,
+        },
+        * child: BlockChild,
+    },
+    GotoStatement ("goto_statement") {
+        fields: {
+            label: StatementIdentifier,
+        },
+    },
+    StatementIdentifier ("statement_identifier") {
+        @src,
+    },
     InitDeclarator ("init_declarator") {
         fields: {
             * declarator: Declarator,
-            * value: Expression,
+            * value: Initializer,
         },
     },
+    ArrayDeclarator ("array_declarator") {
+        fields: {
+            * declarator: Declarator,
+            ?* size: Expression,
+        },
+    },
+    InitializerList ("initializer_list") {
+        * children: Initializer,
+    },
     ElseClause ("else_clause") {
         * child: Statement,
     },
@@ -584,8 +1039,8 @@ declare_nodes! {
     },
     ParameterDeclaration ("parameter_declaration") {
         fields: {
-            * declarator: Declarator,
             * r#type: TypeSpecifier,
+            ?* declarator: Declarator,
         },
     },
     UpdateExpression ("update_expression") {
@@ -594,6 +1049,12 @@ declare_nodes! {
             * operator: UpdateOperator,
         },
     },
+    UnaryExpression ("unary_expression") {
+        fields: {
+            * argument: Expression,
+            * operator: UnaryOperator,
+        },
+    },
     TypeDefinition ("type_definition") {
         fields: {
             * declarator: TypeDeclarator,
@@ -603,22 +1064,84 @@ declare_nodes! {
     TypeIdentifier ("type_identifier") {
         @src,
     },
+    StructSpecifier ("struct_specifier") {
+        fields: {
+            ? name: TypeIdentifier,
+            ? body: FieldDeclarationList,
+        },
+    },
+    FieldDeclarationList ("field_declaration_list") {
+        children: FieldDeclaration,
+    },
+    FieldDeclaration ("field_declaration") {
+        fields: {
+            * declarator: Declarator,
+            * r#type: TypeSpecifier,
+        },
+    },
+    FieldIdentifier ("field_identifier") {
+        @src,
+    },
+    EnumSpecifier ("enum_specifier") {
+        fields: {
+            ? name: TypeIdentifier,
+            ? body: EnumeratorList,
+        },
+    },
+    EnumeratorList ("enumerator_list") {
+        children: Enumerator,
+    },
+    Enumerator ("enumerator") {
+        fields: {
+            name: Identifier,
+            ?* value: Expression,
+        },
+    },
     ~ True ("true"),
     ~ False ("false"),
     ~ AssignEquals ("="),
     ~ EqualsCheck ("=="),
     ~ NotEqualsCheck ("!="),
+    ~ LessThan ("<"),
+    ~ GreaterThan (">"),
+    ~ LessThanOrEqual ("<="),
+    ~ GreaterThanOrEqual (">="),
+    ~ LogicalAnd ("&&"),
+    ~ LogicalOr ("||"),
+    ~ Not ("!"),
     ~ Plus ("+"),
     ~ Minus ("-"),
+    ~ Times ("*"),
+    ~ Divide ("/"),
+    ~ Modulo ("%"),
+    ~ BitwiseAnd ("&"),
+    ~ BitwiseOr ("|"),
+    ~ BitwiseXor ("^"),
+    ~ BitwiseNot ("~"),
+    ~ ShiftLeft ("<<"),
+    ~ ShiftRight (">>"),
     ~ PlusEquals ("+="),
     ~ MinusEquals ("-="),
+    ~ TimesEquals ("*="),
+    ~ DivideEquals ("/="),
+    ~ ModuloEquals ("%="),
+    ~ AndEquals ("&="),
+    ~ OrEquals ("|="),
+    ~ XorEquals ("^="),
+    ~ ShiftLeftEquals ("<<="),
+    ~ ShiftRightEquals (">>="),
     ~ PlusPlus ("++"),
     ~ MinusMinus ("--"),
+    ~ Dot ("."),
+    ~ Arrow ("->"),
+    ~ BreakStatement ("break_statement"),
+    ~ ContinueStatement ("continue_statement"),
 }
 
 /// Parses C file (`src`) and returns the top-level
-/// node of the file, a `TranslationUnit`.
-pub fn parse(src: &str) -> TranslationUnit {
+/// node of the file, a `TranslationUnit`, or every problem
+/// that prevented it from being converted.
+pub fn parse(src: &str) -> Result<TranslationUnit, ParseError> {
     let mut parser = tree_sitter::Parser::new();
     parser
         .set_language(&tree_sitter_c::LANGUAGE.into())
@@ -626,8 +1149,25 @@ pub fn parse(src: &str) -> TranslationUnit {
     let tree = parser.parse(src, None).unwrap();
     let root = tree.root_node();
 
-    match Node::from_old(root, src) {
-        Node::TranslationUnit(tu) => *tu,
-        _ => panic!("Root node was not a translation unit"),
+    let mut issues = vec![];
+
+    find_syntax_issues(root, &mut issues);
+    let translation_unit = Node::from_old(root, src, &mut issues);
+
+    // what several lowered nodes share (e.g., the type of `int a, b;`)
+    // gets converted, and so checked, for each of them
+    let mut unique: Issues = vec![];
+    for issue in issues {
+        if !unique.contains(&issue) {
+            unique.push(issue);
+        }
+    }
+    let mut issues = unique;
+
+    issues.sort_by_key(|(_, span)| span.start_byte);
+
+    match translation_unit {
+        Some(Node::TranslationUnit(tu)) if issues.is_empty() => Ok(*tu),
+        _ => Err(ParseError { issues }),
     }
 }
@@ -0,0 +1,446 @@
+//! Compiles C programs with cranium and checks what running them
+//! prints, or how compiling them fails.
+
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+/// Saves `source` as `name` (in the test scratch directory) and runs
+/// it with `input` on stdin.
+fn run(name: &str, source: &str, input: &str) -> Output {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, source).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cranium"))
+        .arg("run")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+
+    child.wait_with_output().unwrap()
+}
+
+/// Asserts that the C program `source` prints `expected` given `input`.
+#[track_caller]
+fn assert_prints_with_input(name: &str, source: &str, input: &str, expected: &str) {
+    let output = run(&format!("{name}.c"), source, input);
+
+    assert!(
+        output.status.success(),
+        "{name} failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+}
+
+/// Asserts that the C program `source` prints `expected`.
+#[track_caller]
+fn assert_prints(name: &str, source: &str, expected: &str) {
+    assert_prints_with_input(name, source, "", expected);
+}
+
+/// Asserts that running the program `file` fails with an error
+/// mentioning `message`.
+#[track_caller]
+fn assert_fails(file: &str, source: &str, message: &str) {
+    let output = run(file, source, "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(
+        output.status.code(),
+        Some(1),
+        "{file} didn't fail:\n{stderr}"
+    );
+    assert!(
+        stderr.contains(message),
+        "{file} failed otherwise:\n{stderr}"
+    );
+}
+
+#[test]
+fn arithmetic() {
+    assert_prints(
+        "arithmetic",
+        r#"
+int main() {
+  int a = 17, b = -5;
+  long big = 100000;
+  printf("%d %d %d %d %d\n", a + b, a - b, a * b, a / b, a % b);
+  printf("%d %d %d\n", -a / 5, -a % 5, (a + 3) * 2 - 1);
+  printf("%d %d\n", big * 3, big / 7);
+  printf("%d %d\n", a / 0, a % 0);
+  return 0;
+}
+"#,
+        "12 22 -85 -3 2\n-3 -2 39\n300000 14285\n-1 17\n",
+    );
+}
+
+#[test]
+fn overflow_wraps_around() {
+    assert_prints(
+        "overflow_wraps_around",
+        r#"
+int main() {
+  int i = 32767;
+  unsigned u = 0;
+  char c = 127;
+  i++;
+  u--;
+  c += 1;
+  printf("%d %u %d\n", i, u, c);
+  return 0;
+}
+"#,
+        "-32768 65535 -128\n",
+    );
+}
+
+#[test]
+fn integer_promotion() {
+    assert_prints(
+        "integer_promotion",
+        r#"
+int main() {
+  unsigned char x = 200, y = 100;
+  char c = -1;
+  unsigned char z = 0;
+  unsigned short s = 1;
+  printf("%d %d %d\n", x + y, x * 2, c < 1u);
+  printf("%d %d\n", -1 < s, z - 1);
+  return 0;
+}
+"#,
+        "300 400 0\n0 -1\n",
+    );
+}
+
+#[test]
+fn comparison() {
+    assert_prints(
+        "comparison",
+        r#"
+int main() {
+  int a = -3, b = 2;
+  unsigned u = 2;
+  printf("%d%d%d%d%d%d\n", a < b, a > b, a <= -3, b >= 3, a == -3, a != b);
+  printf("%d%d\n", a < u, false < true);
+  printf("%d%d%d\n", a < b && b < 1, a < b || b < 1, !(a < b));
+  return 0;
+}
+"#,
+        "101011\n01\n010\n",
+    );
+}
+
+#[test]
+fn logical_operators_short_circuit() {
+    assert_prints(
+        "logical_operators_short_circuit",
+        r#"
+int calls = 0;
+
+bool touch(bool value) {
+  calls++;
+  return value;
+}
+
+int main() {
+  bool a = false && touch(true);
+  bool b = true || touch(false);
+  bool c = true && touch(true);
+  printf("%d%d%d %d\n", a, b, c, calls);
+  return 0;
+}
+"#,
+        "011 1\n",
+    );
+}
+
+#[test]
+fn bitwise_operators() {
+    assert_prints(
+        "bitwise_operators",
+        r#"
+int main() {
+  int a = 12, b = 10, n = -16;
+  unsigned u = 0x8000;
+  printf("%d %d %d %d\n", a & b, a | b, a ^ b, ~a);
+  printf("%d %d %u %d\n", 1 << 4, n >> 2, u >> 15, 1 << 16);
+  return 0;
+}
+"#,
+        "8 14 6 -13\n16 -4 1 0\n",
+    );
+}
+
+#[test]
+fn number_literals() {
+    assert_prints(
+        "number_literals",
+        r#"
+int main() {
+  printf("%d %d %x %u\n", 0x1F, 017, 0xFFFFu, 40000);
+  printf("%u %d\n", 65535u + 1, 70000L / 7);
+  return 0;
+}
+"#,
+        "31 15 ffff 40000\n0 10000\n",
+    );
+}
+
+#[test]
+fn printf_conversions() {
+    assert_prints(
+        "printf_conversions",
+        r#"
+int main() {
+  char name[] = "cranium";
+  printf("%c%c %d %u %x\n", 'o', 107, -42, -1, 48879);
+  printf("%s and %s: 100%%\n", name, "BF");
+  printf("%d %u\n", true, false);
+  return 0;
+}
+"#,
+        "ok -42 65535 beef\ncranium and BF: 100%\n1 0\n",
+    );
+}
+
+#[test]
+fn strings_and_characters() {
+    assert_prints(
+        "strings_and_characters",
+        r#"
+int main() {
+  char word[6] = "hey";
+  puts("line");
+  print("no newline ");
+  putchar(word[0]);
+  putchar(word[2] - 'a' + 'A');
+  putchar('\n');
+  printf("%d\n", word[3]);
+  return 0;
+}
+"#,
+        "line\nno newline hY\n0\n",
+    );
+}
+
+#[test]
+fn functions_and_recursion() {
+    assert_prints(
+        "functions_and_recursion",
+        r#"
+int add(int a, int b) {
+  return a + b;
+}
+
+int fib(int n) {
+  if (n < 2) {
+    return n;
+  }
+  return fib(n - 1) + fib(n - 2);
+}
+
+void greet(char c) {
+  putchar(c);
+  putchar('\n');
+}
+
+int main() {
+  greet('!');
+  printf("%d %d\n", add(40, 2), fib(10));
+  return 0;
+}
+"#,
+        "!\n42 55\n",
+    );
+}
+
+#[test]
+fn control_flow() {
+    assert_prints(
+        "control_flow",
+        r#"
+int main() {
+  for (int i = 0; i < 10; i++) {
+    if (i % 2 == 0) {
+      continue;
+    }
+    if (i > 7) {
+      break;
+    }
+    printf("%d", i);
+  }
+
+  int n = 3;
+  do {
+    printf(" %d", n);
+  } while (--n);
+
+  while (n < 3) {
+    n += 1;
+  }
+
+  switch (n) {
+    case 2:
+      print(" two");
+    case 3:
+      print(" three");
+    case 4:
+      print(" four");
+      break;
+    default:
+      print(" other");
+  }
+
+  int k = 0;
+again:
+  k++;
+  if (k < 4) {
+    goto again;
+  }
+  printf(" %d\n", k);
+  return 0;
+}
+"#,
+        "1357 3 2 1 three four 4\n",
+    );
+}
+
+#[test]
+fn arrays_structs_and_enums() {
+    assert_prints(
+        "arrays_structs_and_enums",
+        r#"
+enum color { RED, GREEN = 5, BLUE };
+
+struct point {
+  int x;
+  int y;
+};
+
+int main() {
+  int grid[2][3] = {{1, 2, 3}, {4, 5, 6}};
+  struct point p = {3, -4};
+  struct point q;
+  int i = 1;
+  q = p;
+  q.y += grid[i][i + 1];
+  grid[i][0] = BLUE;
+  printf("%d %d %d %d %d\n", grid[1][0], grid[0][2], q.x, q.y, GREEN);
+  return 0;
+}
+"#,
+        "6 3 3 2 5\n",
+    );
+}
+
+#[test]
+fn conditional_comma_and_update() {
+    assert_prints(
+        "conditional_comma_and_update",
+        r#"
+int main() {
+  int a = 5, b;
+  int arr[2] = {10, 20};
+  b = (a++, a * 2);
+  printf("%d %d %d\n", a, b, a > 5 ? 1 : 2);
+  printf("%d %d %d\n", arr[0]++, ++arr[1], arr[0]);
+  return 0;
+}
+"#,
+        "6 12 1\n10 21 11\n",
+    );
+}
+
+#[test]
+fn globals_statics_and_constants() {
+    assert_prints(
+        "globals_statics_and_constants",
+        r#"
+enum { N = 3, M = N * 2 + 1 };
+
+char buf[N + 1] = "abc";
+int table[M] = {N, -N, N > 2 ? 7 : 8};
+long total = 40000 * 2;
+
+int counter() {
+  static int count = M;
+  return count++;
+}
+
+int main() {
+  counter();
+  printf("%s %d %d %d %d %d\n", buf, table[0], table[1], table[2], total, counter());
+  switch (4) {
+    case N + 1:
+      puts("four");
+  }
+  return 0;
+}
+"#,
+        "abc 3 -3 7 80000 8\nfour\n",
+    );
+}
+
+#[test]
+fn getchar_reads_input() {
+    assert_prints_with_input(
+        "getchar_reads_input",
+        r#"
+int main() {
+  int c = getchar();
+  while (c != '\n') {
+    putchar(c - 'a' + 'A');
+    c = getchar();
+  }
+  putchar('\n');
+  return 0;
+}
+"#,
+        "shout\n",
+        "SHOUT\n",
+    );
+}
+
+#[test]
+fn invalid_programs_fail() {
+    assert_fails(
+        "undeclared.c",
+        "int main() { return x; }",
+        "use of undeclared variable `x`",
+    );
+    assert_fails(
+        "mismatched.c",
+        "struct s { int a; };\nint main() { struct s v; int i = v; return 0; }",
+        "mismatched types",
+    );
+    assert_fails(
+        "nonconstant.c",
+        "int x;\nint y = x + 1;\nint main() { return 0; }",
+        "initializer of `y` must be a constant",
+    );
+    assert_fails(
+        "printf.c",
+        r#"int main() { printf("%f", 1); return 0; }"#,
+        "unsupported conversion",
+    );
+}
+
+#[test]
+fn head_off_tape_fails() {
+    assert_fails("left.bf", "<+", "the head moved off the tape");
+}